@@ -0,0 +1,284 @@
+use crate::msweeper::{Msweeper, Space, SpaceState, SpaceType};
+use crate::point::Point;
+use serde::Serialize;
+use std::fs;
+use std::path::Path;
+
+// 盤面をテキストで表す。1文字が1マス
+//   '.' 未開封の安全なマス    '*' 未開封の地雷
+//   'F' 旗を立てた地雷        'f' 旗を立てた安全なマス
+//   'o' 開いたマス            '0'-'8' 開いたマスと周囲の地雷数
+// '#'で始まる行と空行は読み飛ばす
+const SAFE: char = '.';
+const MINE: char = '*';
+const FLAG_MINE: char = 'F';
+const FLAG_SAFE: char = 'f';
+const OPENED: char = 'o';
+const COMMENT: char = '#';
+
+fn _mine_count(board: &[Vec<Space>], x: usize, y: usize) -> usize {
+    let p = Point::<usize> { x, y }.utoi();
+    let mut rv = 0;
+    for pos8 in Point::pos8_iter() {
+        let n = p.get_pos_8(pos8);
+        if (n.x < 0) || (n.y < 0) {
+            continue;
+        }
+        let Some(sp) = board
+            .get(n.y as usize)
+            .and_then(|row| row.get(n.x as usize))
+        else {
+            continue;
+        };
+        if sp.stype == SpaceType::MINE {
+            rv += 1;
+        }
+    }
+    return rv;
+}
+
+// テキストから盤面を作る。エラーには1始まりの行と列を付ける
+pub fn parse(text: &str) -> Result<Vec<Vec<Space>>, String> {
+    let mut board: Vec<Vec<Space>> = Vec::new();
+    // 数字で書かれたマスの位置と行番号、列番号
+    let mut numbers = Vec::new();
+    let mut first_line = 0;
+    for (i, line) in text.lines().enumerate() {
+        let line_no = i + 1;
+        let line = line.trim_end();
+        if line.is_empty() || line.starts_with(COMMENT) {
+            continue;
+        }
+        let mut row = Vec::new();
+        for (j, c) in line.chars().enumerate() {
+            let column = j + 1;
+            let (state, stype) = match c {
+                SAFE => (SpaceState::UNOPENED, SpaceType::EMPTY),
+                MINE => (SpaceState::UNOPENED, SpaceType::MINE),
+                FLAG_MINE => (SpaceState::FLAG, SpaceType::MINE),
+                FLAG_SAFE => (SpaceState::FLAG, SpaceType::EMPTY),
+                OPENED => (SpaceState::OPENED, SpaceType::EMPTY),
+                '0'..='8' => {
+                    let num = c.to_digit(10).unwrap() as usize;
+                    numbers.push((
+                        Point::<usize> {
+                            x: j,
+                            y: board.len(),
+                        },
+                        num,
+                        line_no,
+                        column,
+                    ));
+                    (SpaceState::OPENED, SpaceType::EMPTY)
+                }
+                _ => {
+                    return Err(format!(
+                        "line {}, column {}: unexpected character '{}'. Use '{}', '{}', '{}', '{}', '{}' or a number.",
+                        line_no, column, c, SAFE, MINE, FLAG_MINE, FLAG_SAFE, OPENED
+                    ));
+                }
+            };
+            row.push(Space { state, stype });
+        }
+        if let Some(first) = board.first() {
+            if row.len() != first.len() {
+                return Err(format!(
+                    "line {}: expected {} cells like line {}, found {}.",
+                    line_no,
+                    first.len(),
+                    first_line,
+                    row.len()
+                ));
+            }
+        } else {
+            first_line = line_no;
+        }
+        board.push(row);
+    }
+    if board.is_empty() {
+        return Err("the board is empty.".to_string());
+    }
+
+    for (p, num, line_no, column) in numbers {
+        let count = _mine_count(&board, p.x, p.y);
+        if count != num {
+            return Err(format!(
+                "line {}, column {}: the number {} does not match the {} mines around it.",
+                line_no, column, num, count
+            ));
+        }
+    }
+    return Ok(board);
+}
+
+pub fn load(path: &Path) -> Result<Vec<Vec<Space>>, String> {
+    let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    return parse(&text).map_err(|e| format!("{}: {}", path.display(), e));
+}
+
+// 盤面をテキストにする。開いたマスは周囲の地雷数で表す
+pub fn format(ms: &Msweeper) -> Vec<String> {
+    let mut rv = Vec::new();
+    for y in 0..ms.height() {
+        let mut line = String::new();
+        for x in 0..ms.width() {
+            let p = Point::<usize> { x, y };
+            let space = ms.space(&p);
+            let c = match (&space.state, &space.stype) {
+                // ?の印は残さない
+                (SpaceState::UNOPENED | SpaceState::QUESTION, SpaceType::EMPTY) => SAFE,
+                (SpaceState::UNOPENED | SpaceState::QUESTION, SpaceType::MINE) => MINE,
+                (SpaceState::FLAG, SpaceType::MINE) => FLAG_MINE,
+                (SpaceState::FLAG, SpaceType::EMPTY) => FLAG_SAFE,
+                // 踏んだ地雷
+                (SpaceState::OPENED, SpaceType::MINE) => MINE,
+                (SpaceState::OPENED, SpaceType::EMPTY) => {
+                    char::from_digit(ms.mine_count(&p) as u32, 10).unwrap()
+                }
+            };
+            line.push(c);
+        }
+        rv.push(line);
+    }
+    return rv;
+}
+
+// 外部のスクリプトで読むための1マス分の情報
+#[derive(Serialize)]
+pub struct CellDump {
+    pub state: SpaceState,
+    pub mine: bool,
+    // 周囲の地雷数。開いていないマスも含めて計算する
+    pub number: usize,
+}
+
+// 盤面全体の情報。座標は0始まりの[x, y]
+#[derive(Serialize)]
+pub struct Dump {
+    pub width: usize,
+    pub height: usize,
+    pub mine_num: usize,
+    pub seed: u64,
+    // falseなら最初に開くまで地雷は置かれていない
+    pub started: bool,
+    pub mines: Vec<[usize; 2]>,
+    // cells[y][x]
+    pub cells: Vec<Vec<CellDump>>,
+}
+
+pub fn dump(ms: &Msweeper) -> Dump {
+    let mut mines = Vec::new();
+    let mut cells = Vec::new();
+    for y in 0..ms.height() {
+        let mut row = Vec::new();
+        for x in 0..ms.width() {
+            let p = Point::<usize> { x, y };
+            let space = ms.space(&p);
+            let mine = space.stype == SpaceType::MINE;
+            if mine {
+                mines.push([x, y]);
+            }
+            row.push(CellDump {
+                state: space.state.clone(),
+                mine,
+                number: ms.mine_count(&p),
+            });
+        }
+        cells.push(row);
+    }
+    return Dump {
+        width: ms.width(),
+        height: ms.height(),
+        mine_num: ms.mine_num(),
+        seed: ms.seed(),
+        started: ms.is_started(),
+        mines,
+        cells,
+    };
+}
+
+// 大きさとシードをコメントに書いた盤面のテキスト。--boardでそのまま読み込める
+pub fn to_text(ms: &Msweeper) -> String {
+    let mut rv = format!(
+        "{} width: {}, height: {}, mines: {}, seed: {}\n",
+        COMMENT,
+        ms.width(),
+        ms.height(),
+        ms.mine_num(),
+        ms.seed()
+    );
+    if !ms.is_started() {
+        rv += &format!("{} mines are placed on the first click\n", COMMENT);
+    }
+    for line in format(ms) {
+        rv += &line;
+        rv.push('\n');
+    }
+    return rv;
+}
+
+pub fn to_json(ms: &Msweeper) -> String {
+    return serde_json::to_string_pretty(&dump(ms)).unwrap_or_default();
+}
+
+// 拡張子が.jsonならJSON、それ以外はテキストで書き出す
+pub fn export(ms: &Msweeper, path: &Path) -> Result<(), String> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|e| format!("{}: {}", dir.display(), e))?;
+    }
+    let text = if path.extension().is_some_and(|ext| ext == "json") {
+        to_json(ms)
+    } else {
+        to_text(ms)
+    };
+    fs::write(path, text).map_err(|e| format!("{}: {}", path.display(), e))?;
+    return Ok(());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_and_format_round_trip() {
+        let text = "# sample\n*1o.\n11..\n\n....\n.F.f\n";
+        let board = parse(text).unwrap();
+        let mut ms = Msweeper::construct(4, 4, 1, Some(0)).unwrap();
+        ms.set_board(&board).unwrap();
+        assert_eq!(ms.mine_num(), 2);
+        assert_eq!(ms.remain(), 0);
+        assert_eq!(format(&ms), vec!["*10.", "11..", "....", ".F.f"]);
+    }
+
+    #[test]
+    fn errors_point_at_the_cell() {
+        let err = |text: &str| parse(text).err().unwrap();
+        assert_eq!(
+            err("..\n.x\n"),
+            "line 2, column 2: unexpected character 'x'. Use '.', '*', 'F', 'f', 'o' or a number."
+        );
+        assert_eq!(
+            err("# c\n...\n..\n"),
+            "line 3: expected 3 cells like line 2, found 2."
+        );
+        assert_eq!(
+            err("*2\n..\n"),
+            "line 1, column 2: the number 2 does not match the 1 mines around it."
+        );
+    }
+
+    #[test]
+    fn dump_has_numbers_for_every_cell() {
+        let mut ms = Msweeper::construct(3, 2, 1, Some(7)).unwrap();
+        ms.set_board(&parse("*..\n.o.\n").unwrap()).unwrap();
+        let d = dump(&ms);
+        assert_eq!(d.mines, vec![[0, 0]]);
+        assert_eq!(d.cells[0][1].number, 1);
+        assert_eq!(d.cells[0][2].number, 0);
+        assert!(d.cells[1][1].state == SpaceState::OPENED);
+        assert_eq!(
+            to_text(&ms),
+            "# width: 3, height: 2, mines: 1, seed: 7\n*..\n.1.\n"
+        );
+    }
+}
@@ -1,4 +1,15 @@
+use crate::config_file::ConfigFile;
+use crate::keys::{Keys, Layout};
+use crate::msweeper::{FirstClick, Msweeper};
+use crate::preset::{self, Preset};
+use crate::save;
+use crate::theme::Theme;
 use getopts::Options;
+use std::collections::BTreeMap;
+use std::env;
+use std::fmt::Display;
+use std::path::PathBuf;
+use std::str::FromStr;
 
 pub struct Config {
     opts: Options,
@@ -6,6 +17,79 @@ pub struct Config {
     pub width: usize,
     pub height: usize,
     pub mine_num: usize,
+    pub seed: Option<u64>,
+    pub no_guess: bool,
+    pub first_click: FirstClick,
+    pub practice: bool,
+    pub question_marks: bool,
+    pub replay: Option<PathBuf>,
+    // 地雷を置く代わりに読み込む盤面
+    pub board: Option<PathBuf>,
+    // ゲームを始めずに盤面を書き出す先
+    pub export: Option<PathBuf>,
+    // 中断したゲームの保存先
+    pub slot: String,
+    pub resume: bool,
+    // ゲームを始めずに統計を表示する
+    pub stats: bool,
+    // ゲームを始めずに上位の記録を表示する
+    pub leaderboard: bool,
+    // リプレイなどを保存するディレクトリ
+    pub data_dir: PathBuf,
+    pub theme: Theme,
+    pub keys: Keys,
+    // 設定ファイルの[keys]。--keysで選んだ並びに重ねる
+    key_table: toml::Table,
+    // 設定ファイルで定義されたプリセット
+    presets: BTreeMap<String, Preset>,
+}
+
+// XDG Base Directoryに従ったデータの保存先
+fn default_data_dir() -> PathBuf {
+    let base = match env::var_os("XDG_DATA_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => {
+            let home = env::var_os("HOME").unwrap_or_default();
+            PathBuf::from(home).join(".local").join("share")
+        }
+    };
+    return base.join("msweeper-rs");
+}
+
+// XDG Base Directoryに従った設定ファイルの場所
+fn default_config_dir() -> PathBuf {
+    let base = match env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => {
+            let home = env::var_os("HOME").unwrap_or_default();
+            PathBuf::from(home).join(".config")
+        }
+    };
+    return base.join("msweeper-rs");
+}
+
+// 割合に盤面のマス数を掛けて四捨五入した数を地雷数にする。
+// Msweeper::constructと同じく、すべてのマスが地雷になる数は受け付けない
+fn mines_from_density(density: f64, width: usize, height: usize) -> Result<usize, String> {
+    if !(0.0..1.0).contains(&density) {
+        return Err(format!(
+            "Invalid density {}. It must be at least 0 and less than 1.",
+            density
+        ));
+    }
+    let size = width * height;
+    let mine_num = (density * size as f64).round() as usize;
+    if mine_num >= size {
+        return Err(format!(
+            "Density {} gives {} mines on a {}x{} board, but at most {} mines fit.",
+            density,
+            mine_num,
+            width,
+            height,
+            size.saturating_sub(1)
+        ));
+    }
+    return Ok(mine_num);
 }
 
 fn print_usage(program: &str, opts: &Options) {
@@ -19,76 +103,334 @@ impl Config {
     const WIDTH_OPTION: &'static str = "w";
     const MINE_NUM_OPTION: &'static str = "m";
     const HEIGHT_OPTION: &'static str = "e";
+    const SEED_OPTION: &'static str = "s";
+    const NO_GUESS_OPTION: &'static str = "no-guess";
+    const FIRST_CLICK_OPTION: &'static str = "f";
+    const PRACTICE_OPTION: &'static str = "practice";
+    const QUESTION_MARKS_OPTION: &'static str = "question-marks";
+    const KEYS_OPTION: &'static str = "keys";
+    const REPLAY_OPTION: &'static str = "replay";
+    const BOARD_OPTION: &'static str = "board";
+    const EXPORT_OPTION: &'static str = "export";
+    const STATS_OPTION: &'static str = "stats";
+    const LEADERBOARD_OPTION: &'static str = "leaderboard";
+    const PRESET_OPTION: &'static str = "preset";
+    const DENSITY_OPTION: &'static str = "density";
+    const CONFIG_OPTION: &'static str = "config";
+    const CONFIG_FILE_NAME: &'static str = "config.toml";
+    const SLOT_OPTION: &'static str = "slot";
+    const RESUME_OPTION: &'static str = "resume";
+    const DEFAULT_SLOT: &'static str = "default";
     const DEFAULT_WIDTH: usize = 10;
     const DEFAULT_HEIGHT: usize = 10;
     const DEFAULT_MINE_NUM: usize = 10;
 
-    fn parse_optnum(&mut self, matches: &getopts::Matches, opt: &str) -> Result<Option<usize>, ()> {
-        if matches.opt_present(opt) {
-            if let Some(text) = matches.opt_str(opt) {
-                match text.parse::<usize>() {
-                    Ok(number) => {
-                        return Ok(Some(number));
-                    }
-                    Err(msg) => {
-                        eprintln!("Error: {}", msg.to_string());
-                        print_usage(&self.program, &self.opts);
-                        return Err(());
-                    }
+    // エラーの表示に使うオプションの名前
+    fn _opt_name(opt: &str) -> String {
+        if opt.len() == 1 {
+            return format!("-{}", opt);
+        }
+        return format!("--{}", opt);
+    }
+
+    fn parse_optnum<T>(
+        &mut self,
+        matches: &getopts::Matches,
+        opt: &str,
+    ) -> Result<Option<T>, String>
+    where
+        T: FromStr,
+        T::Err: Display,
+    {
+        if let Some(text) = matches.opt_str(opt) {
+            match text.parse::<T>() {
+                Ok(number) => {
+                    return Ok(Some(number));
+                }
+                Err(msg) => {
+                    return Err(format!(
+                        "Invalid value '{}' for {}: {}",
+                        text,
+                        Self::_opt_name(opt),
+                        msg
+                    ));
                 }
-            } else {
-                return Err(());
             }
         }
 
         return Ok(None);
     }
 
-    fn parse_width(&mut self, matches: &getopts::Matches) -> Result<(), ()> {
+    fn _apply_preset(&mut self, name: &str) -> Result<(), String> {
+        let preset = preset::find(name, &self.presets)?;
+        self.width = preset.width;
+        self.height = preset.height;
+        self.mine_num = preset.mine_num;
+        return Ok(());
+    }
+
+    // 設定ファイルの値を既定値として使う。コマンドラインはこの後に読むので上書きできる
+    fn apply_file(&mut self, file: ConfigFile) -> Result<(), String> {
+        self.presets = file.presets;
+        if let Some(name) = &file.preset {
+            self._apply_preset(name)?;
+        }
+        if let Some(width) = file.width {
+            self.width = width;
+        }
+        if let Some(height) = file.height {
+            self.height = height;
+        }
+        if let Some(mines) = file.mines {
+            self.mine_num = mines;
+        }
+        if let Some(first_click) = file.first_click {
+            self.first_click = first_click;
+        }
+        if let Some(question_marks) = file.question_marks {
+            self.question_marks = question_marks;
+        }
+        if let Some(dir) = file.data_dir {
+            // 先頭の~はホームディレクトリにする
+            self.data_dir = match dir.strip_prefix("~") {
+                Ok(rest) => PathBuf::from(env::var_os("HOME").unwrap_or_default()).join(rest),
+                Err(_) => dir,
+            };
+        }
+        self.theme = file.theme;
+        self.key_table = file.keys;
+        return Ok(());
+    }
+
+    // --configで指定されたファイル、なければ~/.config/msweeper-rs/config.tomlがあれば読む
+    fn parse_config(&mut self, matches: &getopts::Matches) -> Result<(), String> {
+        let path = match matches.opt_str(Self::CONFIG_OPTION) {
+            Some(path) => PathBuf::from(path),
+            None => {
+                let path = default_config_dir().join(Self::CONFIG_FILE_NAME);
+                if !path.exists() {
+                    return Ok(());
+                }
+                path
+            }
+        };
+        return self.apply_file(ConfigFile::load(&path)?);
+    }
+
+    // 大きさと地雷数をまとめて決める。-w/-e/-mはこの後に読むので上書きできる
+    fn parse_preset(&mut self, matches: &getopts::Matches) -> Result<(), String> {
+        if let Some(name) = matches.opt_str(Self::PRESET_OPTION) {
+            self._apply_preset(&name)?;
+        }
+        return Ok(());
+    }
+
+    fn parse_width(&mut self, matches: &getopts::Matches) -> Result<(), String> {
         let result = self.parse_optnum(matches, Self::WIDTH_OPTION)?;
-        match result {
-            Some(number) => self.width = number,
-            None => {}
+        if let Some(number) = result {
+            self.width = number;
         }
         return Ok(());
     }
 
-    fn parse_height(&mut self, matches: &getopts::Matches) -> Result<(), ()> {
+    fn parse_height(&mut self, matches: &getopts::Matches) -> Result<(), String> {
         let result = self.parse_optnum(matches, Self::HEIGHT_OPTION)?;
-        match result {
-            Some(number) => self.height = number,
-            None => {}
+        if let Some(number) = result {
+            self.height = number;
         }
         return Ok(());
     }
 
-    fn parse_mine_num(&mut self, matches: &getopts::Matches) -> Result<(), ()> {
+    fn parse_mine_num(&mut self, matches: &getopts::Matches) -> Result<(), String> {
         let result = self.parse_optnum(matches, Self::MINE_NUM_OPTION)?;
-        match result {
-            Some(number) => self.mine_num = number,
-            None => {}
+        if let Some(number) = result {
+            self.mine_num = number;
         }
         return Ok(());
     }
 
-    fn parse_help(&mut self, matches: &getopts::Matches) -> Result<(), ()> {
-        if matches.opt_present(Self::HELP_OPTION) {
-            print_usage(&self.program, &self.opts);
-            return Err(());
+    // 地雷の割合から地雷数を決める。盤面の大きさが決まってから読む
+    fn parse_density(&mut self, matches: &getopts::Matches) -> Result<(), String> {
+        let Some(density) = self.parse_optnum::<f64>(matches, Self::DENSITY_OPTION)? else {
+            return Ok(());
+        };
+        if matches.opt_present(Self::MINE_NUM_OPTION) {
+            return Err("Use either -m or --density, not both.".to_string());
         }
+        self.mine_num = mines_from_density(density, self.width, self.height)?;
+        return Ok(());
+    }
 
+    fn parse_seed(&mut self, matches: &getopts::Matches) -> Result<(), String> {
+        let result = self.parse_optnum(matches, Self::SEED_OPTION)?;
+        if let Some(number) = result {
+            self.seed = Some(number);
+        }
         return Ok(());
     }
 
-    // constructor
-    pub fn new(args: &[String]) -> Result<Config, ()> {
+    // 地雷が多すぎると推測なしの盤面は作れないので先に断る
+    fn parse_no_guess(&mut self, matches: &getopts::Matches) -> Result<(), String> {
+        if !matches.opt_present(Self::NO_GUESS_OPTION) {
+            return Ok(());
+        }
+        self.no_guess = true;
+        let size = self.width * self.height;
+        if (size > 0) && (self.mine_num as f64 > size as f64 * Msweeper::NO_GUESS_MAX_DENSITY) {
+            return Err(format!(
+                "--no-guess allows at most {}% mines, but {} mines on a {}x{} board is {:.1}%.",
+                Msweeper::NO_GUESS_MAX_DENSITY * 100.0,
+                self.mine_num,
+                self.width,
+                self.height,
+                self.mine_num as f64 * 100.0 / size as f64
+            ));
+        }
+        return Ok(());
+    }
+
+    fn parse_first_click(&mut self, matches: &getopts::Matches) -> Result<(), String> {
+        let result = self.parse_optnum(matches, Self::FIRST_CLICK_OPTION)?;
+        if let Some(first_click) = result {
+            self.first_click = first_click;
+        }
+        return Ok(());
+    }
+
+    fn parse_practice(&mut self, matches: &getopts::Matches) -> Result<(), String> {
+        if matches.opt_present(Self::PRACTICE_OPTION) {
+            self.practice = true;
+        }
+        return Ok(());
+    }
+
+    fn parse_question_marks(&mut self, matches: &getopts::Matches) -> Result<(), String> {
+        if matches.opt_present(Self::QUESTION_MARKS_OPTION) {
+            self.question_marks = true;
+        }
+        return Ok(());
+    }
+
+    fn parse_replay(&mut self, matches: &getopts::Matches) -> Result<(), String> {
+        if let Some(path) = matches.opt_str(Self::REPLAY_OPTION) {
+            self.replay = Some(PathBuf::from(path));
+        }
+        return Ok(());
+    }
+
+    fn parse_board(&mut self, matches: &getopts::Matches) -> Result<(), String> {
+        if let Some(path) = matches.opt_str(Self::BOARD_OPTION) {
+            self.board = Some(PathBuf::from(path));
+        }
+        return Ok(());
+    }
+
+    fn parse_export(&mut self, matches: &getopts::Matches) -> Result<(), String> {
+        if let Some(path) = matches.opt_str(Self::EXPORT_OPTION) {
+            self.export = Some(PathBuf::from(path));
+        }
+        return Ok(());
+    }
+
+    fn parse_slot(&mut self, matches: &getopts::Matches) -> Result<(), String> {
+        if let Some(slot) = matches.opt_str(Self::SLOT_OPTION) {
+            save::slot_path(&self.data_dir, &slot)?;
+            self.slot = slot;
+        }
+        if matches.opt_present(Self::RESUME_OPTION) {
+            self.resume = true;
+        }
+        return Ok(());
+    }
+
+    fn parse_stats(&mut self, matches: &getopts::Matches) -> Result<(), String> {
+        if matches.opt_present(Self::STATS_OPTION) {
+            self.stats = true;
+        }
+        return Ok(());
+    }
+
+    fn parse_leaderboard(&mut self, matches: &getopts::Matches) -> Result<(), String> {
+        if matches.opt_present(Self::LEADERBOARD_OPTION) {
+            self.leaderboard = true;
+        }
+        return Ok(());
+    }
+
+    fn parse_keys(&mut self, matches: &getopts::Matches) -> Result<(), String> {
+        let layout = self.parse_optnum::<Layout>(matches, Self::KEYS_OPTION)?;
+        self.keys = Keys::from_table(&self.key_table, layout)?;
+        return Ok(());
+    }
+
+    fn parse_args(&mut self, matches: &getopts::Matches) -> Result<(), String> {
+        self.parse_preset(matches)?;
+        self.parse_width(matches)?;
+        self.parse_mine_num(matches)?;
+        self.parse_height(matches)?;
+        self.parse_density(matches)?;
+        self.parse_seed(matches)?;
+        self.parse_no_guess(matches)?;
+        self.parse_first_click(matches)?;
+        self.parse_practice(matches)?;
+        self.parse_question_marks(matches)?;
+        self.parse_replay(matches)?;
+        self.parse_board(matches)?;
+        self.parse_export(matches)?;
+        self.parse_slot(matches)?;
+        self.parse_stats(matches)?;
+        self.parse_leaderboard(matches)?;
+        self.parse_keys(matches)?;
+        return Ok(());
+    }
+
+    // 設定ファイル、プリセット、オプションを合わせた結果が遊べる盤面か確かめる
+    fn check_board(&self) -> Result<(), String> {
+        if (self.width == 0) || (self.height == 0) {
+            return Err(format!(
+                "The board must be at least 1x1, but it is {}x{}.",
+                self.width, self.height
+            ));
+        }
+        let size = self.width * self.height;
+        if self.mine_num >= size {
+            return Err(format!(
+                "{} mines do not fit on a {}x{} board; use at most {}.",
+                self.mine_num,
+                self.width,
+                self.height,
+                size - 1
+            ));
+        }
+        return Ok(());
+    }
+
+    // 既定値とオプションの定義だけを持つConfigを作る
+    fn _construct(program: &str) -> Config {
         // set default
         let mut cfg = Config {
             width: Self::DEFAULT_WIDTH,
             height: Self::DEFAULT_HEIGHT,
             mine_num: Self::DEFAULT_MINE_NUM,
+            seed: None,
+            no_guess: false,
+            first_click: FirstClick::SafeCell,
+            practice: false,
+            question_marks: false,
+            replay: None,
+            board: None,
+            export: None,
+            slot: Self::DEFAULT_SLOT.to_string(),
+            resume: false,
+            stats: false,
+            leaderboard: false,
+            data_dir: default_data_dir(),
+            theme: Theme::default(),
+            keys: Keys::default(),
+            key_table: toml::Table::new(),
+            presets: BTreeMap::new(),
             opts: Options::new(),
-            program: args[0].clone(),
+            program: program.to_string(),
         };
 
         // オプションを設定
@@ -104,22 +446,244 @@ impl Config {
             "mine num in the board",
             "NUM",
         );
+        cfg.opts.optopt(
+            "",
+            Self::CONFIG_OPTION,
+            "read defaults from FILE instead of ~/.config/msweeper-rs/config.toml",
+            "FILE",
+        );
+        cfg.opts.optopt(
+            "",
+            Self::PRESET_OPTION,
+            "board size and mines: beginner (9x9/10), intermediate (16x16/40), expert (30x16/99) or a [presets.NAME] table in ~/.config/msweeper-rs/config.toml",
+            "NAME",
+        );
+        cfg.opts.optopt(
+            "",
+            Self::DENSITY_OPTION,
+            "fraction of cells that are mines, instead of -m (e.g. 0.206). The mine count is width*height*DENSITY rounded to the nearest integer, halves rounded up",
+            "DENSITY",
+        );
+        cfg.opts.optopt(
+            Self::SEED_OPTION,
+            "seed",
+            "seed of the mine layout (random if omitted)",
+            "NUM",
+        );
+
+        cfg.opts.optopt(
+            Self::FIRST_CLICK_OPTION,
+            "first-click",
+            "first click policy: classic, safe-cell (default) or safe-opening",
+            "POLICY",
+        );
+        cfg.opts.optflag(
+            "",
+            Self::NO_GUESS_OPTION,
+            "generate boards that can be cleared without guessing",
+        );
+
+        cfg.opts.optflag(
+            "",
+            Self::PRACTICE_OPTION,
+            "practice mode: allow undoing a click on a mine",
+        );
+
+        cfg.opts.optflag(
+            "",
+            Self::QUESTION_MARKS_OPTION,
+            "right click cycles flag, ? mark and unopened",
+        );
+
+        cfg.opts.optopt(
+            "",
+            Self::KEYS_OPTION,
+            "key layout: wasd (default) or vi (hjkl to move, 'i' for hints). [keys] in the config file rebinds keys on top of it",
+            "LAYOUT",
+        );
+
+        cfg.opts
+            .optopt("", Self::REPLAY_OPTION, "play back a recorded game", "FILE");
+
+        cfg.opts.optopt(
+            "",
+            Self::BOARD_OPTION,
+            "play a board read from an ASCII map ('*' mine, '.' safe, 'o' opened, 'F' flag)",
+            "FILE",
+        );
+
+        cfg.opts.optopt(
+            "",
+            Self::EXPORT_OPTION,
+            "write the board (from --board, --resume or the options) to FILE and exit. JSON if FILE ends in .json, ASCII map otherwise, '-' for stdout",
+            "FILE",
+        );
+
+        cfg.opts.optopt(
+            "",
+            Self::SLOT_OPTION,
+            "save slot used when quitting and resuming (default: default)",
+            "NAME",
+        );
+        cfg.opts
+            .optflag("", Self::RESUME_OPTION, "resume the game saved in the slot");
+
+        cfg.opts.optflag(
+            "",
+            Self::STATS_OPTION,
+            "print statistics for each board size and exit",
+        );
+
+        cfg.opts.optflag(
+            "",
+            Self::LEADERBOARD_OPTION,
+            "print the best times for each board size and exit",
+        );
+
+        return cfg;
+    }
+
+    // constructor
+    pub fn new(args: &[String]) -> Result<Config, ()> {
+        let mut cfg = Self::_construct(&args[0]);
 
         // 未定義のオプションを指定した場合にエラーメッセージを出力する
         let matches = match cfg.opts.parse(&args[1..]) {
             Ok(m) => m,
             Err(msg) => {
-                eprintln!("Error: {}", msg.to_string());
+                eprintln!("Error: {}", msg);
                 print_usage(&cfg.program, &cfg.opts);
                 return Err(());
             }
         };
 
-        cfg.parse_help(&matches)?;
-        cfg.parse_width(&matches)?;
-        cfg.parse_mine_num(&matches)?;
-        cfg.parse_height(&matches)?;
+        if matches.opt_present(Self::HELP_OPTION) {
+            print_usage(&cfg.program, &cfg.opts);
+            return Err(());
+        }
+        // 設定ファイルの誤りは使い方を出さずに場所だけを示す
+        if let Err(msg) = cfg.parse_config(&matches) {
+            eprintln!("Error: {}", msg);
+            return Err(());
+        }
+        if let Err(msg) = cfg.parse_args(&matches) {
+            eprintln!("Error: {}", msg);
+            print_usage(&cfg.program, &cfg.opts);
+            return Err(());
+        }
+        if let Err(msg) = cfg.check_board() {
+            eprintln!("Error: {}", msg);
+            return Err(());
+        }
+
+        return Ok(cfg);
+    }
+}
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 設定ファイルの内容とコマンドラインを読んだ結果
+    fn config(file: &str, args: &[&str]) -> Result<Config, String> {
+        let mut cfg = Config::_construct("msweeper-rs");
+        let matches = cfg.opts.parse(args).unwrap();
+        cfg.apply_file(toml::from_str(file).unwrap())?;
+        cfg.parse_args(&matches)?;
         return Ok(cfg);
     }
+
+    fn size(cfg: &Config) -> (usize, usize, usize) {
+        return (cfg.width, cfg.height, cfg.mine_num);
+    }
+
+    #[test]
+    fn density_is_rounded_to_the_nearest_mine_count() {
+        assert_eq!(mines_from_density(0.206, 30, 16), Ok(99));
+        assert_eq!(mines_from_density(0.0, 9, 9), Ok(0));
+        // ちょうど半分は切り上げる
+        assert_eq!(mines_from_density(0.5, 3, 1), Ok(2));
+        assert_eq!(mines_from_density(0.125, 2, 2), Ok(1));
+    }
+
+    #[test]
+    fn density_that_leaves_no_safe_cell_is_rejected() {
+        assert_eq!(
+            mines_from_density(0.9, 2, 2),
+            Err("Density 0.9 gives 4 mines on a 2x2 board, but at most 3 mines fit.".to_string())
+        );
+        assert_eq!(
+            mines_from_density(1.0, 9, 9),
+            Err("Invalid density 1. It must be at least 0 and less than 1.".to_string())
+        );
+        assert!(mines_from_density(-0.1, 9, 9).is_err());
+        assert!(mines_from_density(f64::NAN, 9, 9).is_err());
+    }
+
+    #[test]
+    fn options_override_the_preset_from_the_command_line() {
+        let cfg = config("", &["--preset", "expert"]).unwrap();
+        assert_eq!(size(&cfg), (30, 16, 99));
+        let cfg = config(
+            "",
+            &["--preset", "expert", "-w", "20", "-e", "10", "-m", "30"],
+        )
+        .unwrap();
+        assert_eq!(size(&cfg), (20, 10, 30));
+    }
+
+    #[test]
+    fn command_line_overrides_the_config_file() {
+        let file = "preset = \"expert\"\nwidth = 20\n";
+        // 設定ファイルではプリセットの後に書いた値が勝つ
+        assert_eq!(size(&config(file, &[]).unwrap()), (20, 16, 99));
+        assert_eq!(
+            size(&config(file, &["--preset", "beginner"]).unwrap()),
+            (9, 9, 10)
+        );
+        assert_eq!(size(&config(file, &["-m", "50"]).unwrap()), (20, 16, 50));
+    }
+
+    #[test]
+    fn presets_in_the_config_file_are_found_by_name() {
+        let file = "preset = \"small\"\n[presets.small]\nwidth = 5\nheight = 4\nmines = 3\n";
+        assert_eq!(size(&config(file, &[]).unwrap()), (5, 4, 3));
+        assert_eq!(
+            size(&config(file, &["--preset", "small", "-e", "6"]).unwrap()),
+            (5, 6, 3)
+        );
+        // 組み込みのプリセットは設定ファイルで上書きできない
+        let file = "[presets.beginner]\nwidth = 5\nheight = 4\nmines = 3\n";
+        assert_eq!(
+            size(&config(file, &["--preset", "beginner"]).unwrap()),
+            (9, 9, 10)
+        );
+    }
+
+    #[test]
+    fn unknown_preset_is_an_error() {
+        let file = "[presets.small]\nwidth = 5\nheight = 4\nmines = 3\n";
+        assert_eq!(
+            config(file, &["--preset", "huge"]).err(),
+            Some(
+                "Unknown preset 'huge'. Available presets: beginner, intermediate, expert, small"
+                    .to_string()
+            )
+        );
+        assert!(config("preset = \"huge\"\n", &[]).is_err());
+    }
+
+    #[test]
+    fn tilde_in_data_dir_is_the_home_directory() {
+        let home = PathBuf::from(env::var_os("HOME").unwrap_or_default());
+        let cfg = config("data_dir = \"~/games/msweeper\"\n", &[]).unwrap();
+        assert_eq!(cfg.data_dir, home.join("games/msweeper"));
+        let cfg = config("data_dir = \"~\"\n", &[]).unwrap();
+        assert_eq!(cfg.data_dir, home);
+        // 途中の~やほかの名前の先頭の~はそのまま
+        let cfg = config("data_dir = \"/tmp/~/x\"\n", &[]).unwrap();
+        assert_eq!(cfg.data_dir, PathBuf::from("/tmp/~/x"));
+        let cfg = config("data_dir = \"~user/x\"\n", &[]).unwrap();
+        assert_eq!(cfg.data_dir, PathBuf::from("~user/x"));
+    }
 }
@@ -0,0 +1,83 @@
+use crate::keys::Keys;
+use crate::msweeper::FirstClick;
+use crate::preset::Preset;
+use crate::theme::Theme;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// ~/.config/msweeper-rs/config.toml の内容。書いていない項目はコマンドラインの既定値を使う
+#[derive(Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ConfigFile {
+    pub preset: Option<String>,
+    pub width: Option<usize>,
+    pub height: Option<usize>,
+    pub mines: Option<usize>,
+    pub first_click: Option<FirstClick>,
+    pub question_marks: Option<bool>,
+    pub data_dir: Option<PathBuf>,
+    #[serde(default)]
+    pub theme: Theme,
+    // 並びの既定値に重ねるので、ここでは書かれたままにしておく
+    #[serde(default)]
+    pub keys: toml::Table,
+    // [presets.名前] で定義する独自のプリセット
+    #[serde(default)]
+    pub presets: BTreeMap<String, Preset>,
+}
+
+impl ConfigFile {
+    pub fn load(path: &Path) -> Result<ConfigFile, String> {
+        let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        return Self::parse(&text, path);
+    }
+
+    // 誤りはどのファイルのものか分かるようにpathを付けて返す
+    fn parse(text: &str, path: &Path) -> Result<ConfigFile, String> {
+        let file: ConfigFile =
+            toml::from_str(text).map_err(|e| format!("{}: {}", path.display(), e))?;
+        Keys::from_table(&file.keys, None).map_err(|e| format!("{}: {}", path.display(), e))?;
+        return Ok(file);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(text: &str) -> Result<ConfigFile, String> {
+        return ConfigFile::parse(
+            text,
+            Path::new("/home/user/.config/msweeper-rs/config.toml"),
+        );
+    }
+
+    #[test]
+    fn errors_name_the_file() {
+        for text in [
+            "width = \n",
+            "width = \"wide\"\n",
+            "colour = 3\n",
+            "[theme]\nflag_fg = \"pink\"\n",
+            "[keys]\nlayout = \"emacs\"\n",
+        ] {
+            let msg = parse(text).err().unwrap();
+            assert!(
+                msg.starts_with("/home/user/.config/msweeper-rs/config.toml: "),
+                "{}",
+                msg
+            );
+        }
+    }
+
+    #[test]
+    fn valid_file_is_read() {
+        let file = parse("width = 20\ndata_dir = \"~/games\"\n[theme]\nflag_fg = \"light-red\"\n")
+            .unwrap();
+        assert_eq!(file.width, Some(20));
+        assert_eq!(file.data_dir, Some(PathBuf::from("~/games")));
+        assert_eq!(file.theme.flag_fg.0, 9);
+    }
+}
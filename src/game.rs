@@ -1,15 +1,59 @@
+use crate::keys::{KeyBinding, Keys};
+use crate::msweeper::{MSChange, Space, SpaceState};
+use crate::playback::Playback;
 use crate::point::Point;
+use crate::renderer::Renderer;
+use crate::timer::Timer;
 use crate::Config;
 use crate::Msweeper;
+use msweeper_rs::board;
+use msweeper_rs::hint;
+use msweeper_rs::leaderboard::{Entry, Leaderboard};
+use msweeper_rs::metrics::{Clicks, Metrics};
+use msweeper_rs::probability;
+use msweeper_rs::replay::{Action, Input, Replay};
+use msweeper_rs::save::{self, SaveData};
+use msweeper_rs::solver::Solver;
+use msweeper_rs::stats::{self, Record};
+use std::path::PathBuf;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
 use termion::event::*;
 use termion::input::TermRead;
 
+// 記録やファイル名に使う今の日時(UNIX時間の秒)
+fn _unix_now() -> u64 {
+    return SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+}
+
 enum MSEvent {
     None,
     LeftClick,
     RightClick,
+    // 両ボタンか中ボタンで押して離した
+    ChordClick,
+    // カーソルを動かす量と、行や列の端などへの移動先
+    Move(isize, isize),
+    Jump(Option<usize>, Option<usize>),
+    // 表示している範囲を動かす量
+    Scroll(isize, isize),
+    Open,
+    Flag,
+    Chord,
     Enter,
+    Probability,
+    Hint,
+    Undo,
+    Redo,
+    Export,
+    Stats,
+    Leaderboard,
+    Help,
     Quit,
 }
 
@@ -31,33 +75,165 @@ pub struct Game {
     mouseflag: isize,
     left_pos: Point<usize>,
     right_pos: Point<usize>,
+    // 両ボタンか中ボタンを押している間はtrue
+    chording: bool,
+    // ボタンを押したマス。ドラッグしてこのマスを出るまでは端でもスクロールしない
+    drag_from: Option<Point<usize>>,
     evt_context: EvtContext,
     loop_flag: bool,
+    // 中断したゲームを保存できずに終了を取りやめた直後ならtrue。もう一度終了すると保存せずに終わる
+    save_failed: bool,
     is_game_end: bool,
+    show_probability: bool,
+    // 盤面が変わって確率を計算し直す必要があるときはtrue
+    probability_stale: bool,
     msweeper: Msweeper,
+    renderer: Renderer,
+    data_dir: PathBuf,
+    // 現在のゲームの入力の記録
+    inputs: Vec<Input>,
+    record_start: Instant,
+    replay_path: Option<PathBuf>,
+    // リプレイの再生中
+    playback: Option<Playback>,
+    status: String,
+    // 最初に開いてからの経過時間
+    timer: Timer,
+    // 中断したときの保存先と、そこから再開したかどうか
+    slot: String,
+    resumed: bool,
+    // 読み込んだ盤面から始めたときの最初の盤面。やり直すときに使う
+    start_board: Option<Vec<String>>,
+    // 統計などの画面を表示している間はtrue
+    screen: bool,
+    // 上位に入った記録の順位と、名前を入力している途中の記録
+    new_record: Option<(usize, Entry)>,
+    clicks: Clicks,
+    keys: Keys,
+    // キーボードで操作するマスと、移動キーの前に入力した回数
+    cursor: Point<usize>,
+    count: usize,
 }
 
 impl Game {
     const LEFT: isize = 1;
     const RIGHT: isize = 2;
+    const MIDDLE: isize = 4;
+    const REPLAY_TICK_MS: u64 = 10;
+    const CLOCK_TICK_MS: u64 = 1000;
+    const MAX_COUNT: usize = 9999;
+    const WHEEL_STEP: isize = 3;
 
-    pub fn construct(cfg: &Config) -> Game {
-        let result = Msweeper::construct(cfg.width, cfg.height, cfg.mine_num);
-        let msweeper = match result {
-            Ok(ms) => ms,
-            Err(e) => {
-                panic!("{}", e);
-            }
+    pub fn construct(cfg: &Config) -> Result<Game, String> {
+        let mut msweeper = Msweeper::construct(cfg.width, cfg.height, cfg.mine_num, cfg.seed)?;
+        msweeper.set_no_guess(cfg.no_guess);
+        msweeper.set_first_click(cfg.first_click.clone());
+        msweeper.set_practice(cfg.practice);
+        msweeper.set_question_marks(cfg.question_marks);
+
+        return Ok(Game::_construct_with(cfg, msweeper));
+    }
+
+    // リプレイを再生するゲームを作る
+    pub fn construct_replay(cfg: &Config, replay: Replay) -> Result<Game, String> {
+        let mut msweeper = Msweeper::construct(
+            replay.width,
+            replay.height,
+            replay.mine_num,
+            Some(replay.seed),
+        )?;
+        msweeper.set_mine_layout(&replay.mine_layout())?;
+        let start_board = if replay.board.is_empty() {
+            None
+        } else {
+            msweeper.set_board(&board::parse(&replay.board.join("\n"))?)?;
+            Some(replay.board.clone())
         };
-        let game = Game {
+        msweeper.set_practice(true);
+        msweeper.set_question_marks(replay.question_marks);
+        let mut game = Game::_construct_with(cfg, msweeper);
+        game.start_board = start_board;
+        game.playback = Some(Playback::construct(replay));
+        return Ok(game);
+    }
+
+    // 読み込んだ盤面で遊ぶゲームを作る
+    pub fn construct_board(cfg: &Config, spaces: Vec<Vec<Space>>) -> Result<Game, String> {
+        let height = spaces.len();
+        let width = spaces[0].len();
+        let mut msweeper = Msweeper::construct(width, height, 0, cfg.seed)?;
+        msweeper.set_board(&spaces)?;
+        if msweeper.is_finished() {
+            return Err("The board is already cleared.".to_string());
+        }
+        msweeper.set_practice(cfg.practice);
+        msweeper.set_question_marks(cfg.question_marks);
+        let mut game = Game::_construct_with(cfg, msweeper);
+        game.start_board = Some(board::format(&game.msweeper));
+        return Ok(game);
+    }
+
+    // 保存したスロットからゲームを再開する
+    pub fn construct_resume(cfg: &Config) -> Result<Game, String> {
+        let path = save::slot_path(&cfg.data_dir.join("saves"), &cfg.slot)?;
+        let data = SaveData::load(&path)?;
+        let mut msweeper = Msweeper::from_state(data.msweeper)?;
+        msweeper.set_question_marks(cfg.question_marks);
+        if !msweeper.is_started() || msweeper.is_finished() {
+            return Err(format!("{}: no game in progress.", path.display()));
+        }
+        let mut game = Game::_construct_with(cfg, msweeper);
+        game.timer = Timer::construct(Duration::from_millis(data.elapsed_ms));
+        game.timer.start();
+        for input in &data.inputs {
+            game.clicks.count(&input.action);
+        }
+        game.inputs = data.inputs;
+        game.record_start = Instant::now() - Duration::from_millis(data.record_ms);
+        game.resumed = true;
+        if !data.start_board.is_empty() {
+            game.start_board = Some(data.start_board);
+        }
+        return Ok(game);
+    }
+
+    fn _construct_with(cfg: &Config, msweeper: Msweeper) -> Game {
+        let mut game = Game {
             mouseflag: 0,
             left_pos: Point::<usize> { x: 0, y: 0 },
             right_pos: Point::<usize> { x: 0, y: 0 },
+            drag_from: None,
+            chording: false,
             evt_context: EvtContext::construct(),
             loop_flag: true,
+            save_failed: false,
             is_game_end: false,
+            show_probability: false,
+            probability_stale: false,
             msweeper,
+            renderer: Renderer::construct(&cfg.theme, &cfg.keys),
+            keys: cfg.keys.clone(),
+            data_dir: cfg.data_dir.clone(),
+            inputs: Vec::new(),
+            record_start: Instant::now(),
+            replay_path: None,
+            playback: None,
+            status: String::new(),
+            timer: Timer::construct(Duration::ZERO),
+            slot: cfg.slot.clone(),
+            resumed: false,
+            start_board: None,
+            screen: false,
+            new_record: None,
+            clicks: Clicks::default(),
+            cursor: Point::<usize> { x: 0, y: 0 },
+            count: 0,
+        };
+        game.cursor = Point::<usize> {
+            x: game.msweeper.width() / 2,
+            y: game.msweeper.height() / 2,
         };
+        game.renderer.redraw(&game.msweeper);
 
         return game;
     }
@@ -79,14 +255,78 @@ impl Game {
     }
 
     fn press(&mut self, mb: MouseButton, x: u16, y: u16) {
+        if self.mouseflag == 0 {
+            self.drag_from =
+                self.renderer
+                    .get_board_press_pos(&self.msweeper, x as usize, y as usize);
+        }
         match mb {
             MouseButton::Left => self.press_left(x, y),
             MouseButton::Right => self.press_right(x, y),
-            _ => {}
+            MouseButton::Middle => self.mouseflag |= Self::MIDDLE,
+            MouseButton::WheelUp => self.evt_context.event = MSEvent::Scroll(0, -Self::WHEEL_STEP),
+            MouseButton::WheelDown => self.evt_context.event = MSEvent::Scroll(0, Self::WHEEL_STEP),
+            MouseButton::WheelLeft => {
+                self.evt_context.event = MSEvent::Scroll(-Self::WHEEL_STEP, 0)
+            }
+            MouseButton::WheelRight => {
+                self.evt_context.event = MSEvent::Scroll(Self::WHEEL_STEP, 0)
+            }
+        }
+        let both = Self::LEFT | Self::RIGHT;
+        if ((self.mouseflag & both) == both) || ((self.mouseflag & Self::MIDDLE) != 0) {
+            self.chording = true;
+            self._preview(x, y);
+        }
+    }
+
+    // 押したマスから出て表示している範囲の端までドラッグしたら動かし、
+    // 押していたボタンのクリックは取り消す。端のマスを押したときの手ぶれではスクロールしない
+    fn hold(&mut self, x: u16, y: u16) {
+        let p = self
+            .renderer
+            .get_board_press_pos(&self.msweeper, x as usize, y as usize);
+        let moved = match (&p, &self.drag_from) {
+            (Some(p), Some(from)) => !p.is_equal(from),
+            _ => true,
+        };
+        if moved
+            && self
+                .renderer
+                .edge_scroll(&self.msweeper, x as usize, y as usize)
+        {
+            self.mouseflag = 0;
+            self.chording = false;
+            self.renderer.set_pressed(&self.msweeper, None);
+            return;
         }
+        self._preview(x, y);
+    }
+
+    // 同時押しの間はマウスの下のマスの周りをへこませる
+    fn _preview(&mut self, x: u16, y: u16) {
+        if !self.chording {
+            return;
+        }
+        let p = self
+            .renderer
+            .get_board_press_pos(&self.msweeper, x as usize, y as usize);
+        self.renderer.set_pressed(&self.msweeper, p);
     }
 
     fn release(&mut self, x: u16, y: u16) {
+        if self.chording {
+            // 最初に離したボタンで周りを開き、残りのボタンを離しても何もしない
+            self.chording = false;
+            self.mouseflag = 0;
+            self.renderer.set_pressed(&self.msweeper, None);
+            self.evt_context.event = MSEvent::ChordClick;
+            self.evt_context.pos = Point::<usize> {
+                x: x as usize,
+                y: y as usize,
+            };
+            return;
+        }
         match self.mouseflag {
             Self::LEFT => {
                 if (self.left_pos.x == x as usize) && (self.left_pos.y == y as usize) {
@@ -103,73 +343,762 @@ impl Game {
                 self.mouseflag &= !Self::RIGHT;
             }
             _ => {
-                self.mouseflag = 0; // 複数ボタンは許さない}
+                self.mouseflag = 0;
             }
         }
     }
 
-    fn key_event(&mut self, evt: Event) {
+    // 名前の入力中はキーを文字として扱う
+    fn name_key_event(&mut self, evt: Event) {
+        let Some((_, entry)) = self.new_record.as_mut() else {
+            return;
+        };
         match evt {
-            Event::Key(Key::Char('q')) | Event::Key(Key::Ctrl('c')) => {
-                self.evt_context.event = MSEvent::Quit
+            Event::Key(Key::Char('\n')) => {
+                self._save_record();
+                return;
+            }
+            Event::Key(Key::Esc) => {
+                self.new_record = None;
+                self.renderer.restore(&self.msweeper);
+                return;
+            }
+            Event::Key(Key::Backspace) => {
+                entry.name.pop();
+            }
+            Event::Key(Key::Char(c))
+                if !c.is_control() && entry.name.chars().count() < Leaderboard::NAME_MAX_LEN =>
+            {
+                entry.name.push(c);
+            }
+            _ => return,
+        }
+        self._print_name_prompt();
+    }
+
+    fn key_event(&mut self, evt: Event) {
+        if self.new_record.is_some() {
+            self.name_key_event(evt);
+            return;
+        }
+        if self.screen {
+            // 何かキーを押したら盤面に戻る
+            if let Event::Key(_) = evt {
+                self.screen = false;
+                self.renderer.restore(&self.msweeper);
             }
+            return;
+        }
+        match evt {
             Event::Mouse(me) => match me {
                 MouseEvent::Press(mb, x, y) => self.press(mb, x, y),
                 MouseEvent::Release(x, y) => self.release(x, y),
-                _ => {}
+                MouseEvent::Hold(x, y) => self.hold(x, y),
             },
-            Event::Key(Key::Char('\n')) => self.evt_context.event = MSEvent::Enter,
+            Event::Key(key) => self.evt_context.event = self._key_to_event(key),
             _ => {}
         }
     }
 
+    // 設定されたキーを操作に変える。Ctrl-cはいつでも終了
+    // 移動の前に数字を入力するとその回数だけ動く。行の端への移動では行番号になる
+    fn _key_to_event(&mut self, key: Key) -> MSEvent {
+        if let Key::Char(c @ '0'..='9') = key {
+            if (self.count > 0) || (c != '0') {
+                let digit = c.to_digit(10).unwrap() as usize;
+                self.count = (self.count * 10 + digit).min(Self::MAX_COUNT);
+                return MSEvent::None;
+            }
+        }
+        let count = std::mem::take(&mut self.count);
+        let n = count.max(1) as isize;
+        let (last_x, last_y) = (self.msweeper.width() - 1, self.msweeper.height() - 1);
+        let keys = &self.keys;
+        if (key == Key::Ctrl('c')) || (key == keys.quit.0) {
+            return MSEvent::Quit;
+        }
+        let is_bound = |list: &[KeyBinding]| list.iter().any(|k| k.0 == key);
+        if (key == Key::Left) || is_bound(&keys.left) {
+            return MSEvent::Move(-n, 0);
+        }
+        if (key == Key::Down) || is_bound(&keys.down) {
+            return MSEvent::Move(0, n);
+        }
+        if (key == Key::Up) || is_bound(&keys.up) {
+            return MSEvent::Move(0, -n);
+        }
+        if (key == Key::Right) || is_bound(&keys.right) {
+            return MSEvent::Move(n, 0);
+        }
+        if is_bound(&keys.open) {
+            return MSEvent::Open;
+        }
+        // 範囲の半分ずつ動かす
+        let view = self.renderer.view_size();
+        let (page_x, page_y) = (
+            n * (view.x as isize / 2).max(1),
+            n * (view.y as isize / 2).max(1),
+        );
+        if (key == Key::ShiftLeft) || (key == keys.scroll_left.0) {
+            return MSEvent::Scroll(-page_x, 0);
+        }
+        if (key == Key::ShiftDown) || (key == keys.scroll_down.0) {
+            return MSEvent::Scroll(0, page_y);
+        }
+        if (key == Key::ShiftUp) || (key == keys.scroll_up.0) {
+            return MSEvent::Scroll(0, -page_y);
+        }
+        if (key == Key::ShiftRight) || (key == keys.scroll_right.0) {
+            return MSEvent::Scroll(page_x, 0);
+        }
+        let row = if count > 0 { Some(count - 1) } else { None };
+        let bindings = [
+            (keys.first_column, MSEvent::Jump(Some(0), None)),
+            (keys.last_column, MSEvent::Jump(Some(last_x), None)),
+            (keys.first_row, MSEvent::Jump(None, Some(row.unwrap_or(0)))),
+            (
+                keys.last_row,
+                MSEvent::Jump(None, Some(row.unwrap_or(last_y))),
+            ),
+            (keys.flag, MSEvent::Flag),
+            (keys.chord, MSEvent::Chord),
+            (keys.reset, MSEvent::Enter),
+            (keys.probability, MSEvent::Probability),
+            (keys.hint, MSEvent::Hint),
+            (keys.undo, MSEvent::Undo),
+            (keys.redo, MSEvent::Redo),
+            (keys.export, MSEvent::Export),
+            (keys.stats, MSEvent::Stats),
+            (keys.leaderboard, MSEvent::Leaderboard),
+            (keys.help, MSEvent::Help),
+        ];
+        for (binding, event) in bindings {
+            if binding.0 == key {
+                return event;
+            }
+        }
+        return match key {
+            Key::Home => MSEvent::Jump(Some(0), None),
+            Key::End => MSEvent::Jump(Some(last_x), None),
+            Key::PageUp => MSEvent::Jump(None, Some(0)),
+            Key::PageDown => MSEvent::Jump(None, Some(last_y)),
+            _ => MSEvent::None,
+        };
+    }
+
+    // 保存に失敗したらゲームを失わないように終了せず、理由を表示する
     fn on_event_quit(&mut self) {
+        if !self.save_failed {
+            if let Err(e) = self._save_slot() {
+                self.save_failed = true;
+                self.renderer.print_status(&format!(
+                    "Could not save the game: {}. Press {} again to quit without saving.",
+                    e,
+                    self.keys.quit.name()
+                ));
+                return;
+            }
+        }
         self.loop_flag = false;
     }
 
-    fn on_event_leftclick(&mut self) {
-        let _is_mine = self
-            .msweeper
-            .open(self.evt_context.pos.x, self.evt_context.pos.y);
+    // 途中のゲームをスロットに保存する。再開したゲームが終わっていればスロットを消す
+    fn _save_slot(&mut self) -> Result<(), String> {
+        if self.playback.is_some() {
+            return Ok(());
+        }
+        let path = save::slot_path(&self.data_dir.join("saves"), &self.slot)?;
+        if !self.msweeper.is_started() || self.is_game_end {
+            if self.resumed {
+                let _ = std::fs::remove_file(&path);
+            }
+            return Ok(());
+        }
+        let data = SaveData {
+            msweeper: self.msweeper.to_state(),
+            elapsed_ms: self.timer.elapsed().as_millis() as u64,
+            record_ms: self.record_start.elapsed().as_millis() as u64,
+            inputs: self.inputs.clone(),
+            start_board: self.start_board.clone().unwrap_or_default(),
+        };
+        return data.save(&path);
+    }
+
+    fn _board_pos(&self) -> Option<Point<usize>> {
+        return self.renderer.get_board_press_pos(
+            &self.msweeper,
+            self.evt_context.pos.x,
+            self.evt_context.pos.y,
+        );
+    }
+
+    fn _apply_changes(&mut self, changes: &[MSChange]) {
+        // 終わった時間を表示するので先に時計を止める
+        if changes
+            .iter()
+            .any(|c| matches!(c, MSChange::GameOver | MSChange::GameClear))
+        {
+            self.timer.stop();
+        }
+        if !changes.is_empty() {
+            self.probability_stale = true;
+        }
+        self.renderer.set_elapsed(self.timer.elapsed());
+        self.renderer.apply(&self.msweeper, changes);
+        for change in changes {
+            match change {
+                MSChange::GameOver | MSChange::GameClear => {
+                    self.is_game_end = true;
+                    let time_ms = self.timer.elapsed().as_millis() as u64;
+                    self.renderer.print_metrics(&Metrics::construct(
+                        &self.msweeper,
+                        time_ms,
+                        &self.clicks,
+                    ));
+                    self._save_replay();
+                    self._save_stats(matches!(change, MSChange::GameClear));
+                    if let MSChange::GameClear = change {
+                        self._check_record();
+                    }
+                }
+                MSChange::Resumed => {
+                    self.is_game_end = false;
+                    self.timer.start();
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn _record(&mut self, action: Action, p: &Point<usize>) -> Input {
+        let input = Input {
+            time_ms: self.record_start.elapsed().as_millis() as u64,
+            action,
+            x: p.x,
+            y: p.y,
+        };
+        if self.playback.is_none() {
+            self.inputs.push(input.clone());
+        }
+        return input;
+    }
+
+    // 終わったゲームの盤面と入力をリプレイとして保存する
+    fn _save_replay(&mut self) {
+        if self.playback.is_some() || !self.msweeper.is_started() {
+            return;
+        }
+        let path = match &self.replay_path {
+            Some(path) => path.clone(),
+            None => {
+                let now = _unix_now();
+                let name = format!("replay-{}-{}.json", now, self.msweeper.seed());
+                self.data_dir.join("replays").join(name)
+            }
+        };
+        let board = self.start_board.clone().unwrap_or_default();
+        let replay = Replay::construct(&self.msweeper, board, self.inputs.clone());
+        // 保存に失敗してもゲームは続ける
+        if replay.save(&path).is_ok() {
+            self.replay_path = Some(path);
+        }
+    }
+
+    // 終わったゲームを統計に加える。練習モード、リプレイ、読み込んだ盤面とアンドゥを使ったゲームは数えない
+    fn _save_stats(&mut self, won: bool) {
+        if self.playback.is_some()
+            || self.msweeper.is_practice()
+            || self.start_board.is_some()
+            || self.msweeper.is_undo_used()
+        {
+            return;
+        }
+        let record = Record {
+            width: self.msweeper.width(),
+            height: self.msweeper.height(),
+            mine_num: self.msweeper.mine_num(),
+            won,
+            time_ms: self.timer.elapsed().as_millis() as u64,
+            date: _unix_now(),
+            seed: self.msweeper.seed(),
+        };
+        // 保存に失敗してもゲームは続ける
+        let _ = stats::append(&stats::stats_path(&self.data_dir), &record);
+    }
+
+    // 上位に入ったら名前を聞く。練習モード、読み込んだ盤面、アンドゥやヒントを使ったゲームは載せない
+    fn _check_record(&mut self) {
+        if self.playback.is_some()
+            || self.msweeper.is_practice()
+            || self.start_board.is_some()
+            || self.msweeper.is_undo_used()
+            || (self.msweeper.hint_num() > 0)
+        {
+            return;
+        }
+        let Ok(leaderboard) = Leaderboard::load(&Leaderboard::path(&self.data_dir)) else {
+            return;
+        };
+        let time_ms = self.timer.elapsed().as_millis() as u64;
+        let Some(rank) = leaderboard.rank(
+            self.msweeper.width(),
+            self.msweeper.height(),
+            self.msweeper.mine_num(),
+            time_ms,
+        ) else {
+            return;
+        };
+        let name: String = std::env::var("USER")
+            .unwrap_or_default()
+            .chars()
+            .take(Leaderboard::NAME_MAX_LEN)
+            .collect();
+        let entry = Entry {
+            name,
+            time_ms,
+            date: _unix_now(),
+            seed: self.msweeper.seed(),
+        };
+        self.new_record = Some((rank, entry));
+        self._print_name_prompt();
+    }
+
+    fn _print_name_prompt(&mut self) {
+        let Some((rank, entry)) = &self.new_record else {
+            return;
+        };
+        let prompt = format!(
+            "New best time #{} ({:.3}s)! Enter your name (Enter to save, Esc to skip): {}_",
+            rank + 1,
+            entry.time_ms as f64 / 1000.0,
+            entry.name
+        );
+        self.renderer.print_status(&prompt);
+    }
+
+    fn _save_record(&mut self) {
+        let Some((_, entry)) = self.new_record.take() else {
+            return;
+        };
+        let path = Leaderboard::path(&self.data_dir);
+        let result = Leaderboard::load(&path).and_then(|mut leaderboard| {
+            leaderboard.insert(
+                self.msweeper.width(),
+                self.msweeper.height(),
+                self.msweeper.mine_num(),
+                entry,
+            );
+            leaderboard.save(&path)
+        });
+        match result {
+            Ok(_) => self.on_event_leaderboard(),
+            Err(e) => {
+                self.renderer.restore(&self.msweeper);
+                self.renderer
+                    .print_status(&format!("Failed to save the time: {}", e));
+            }
+        }
+    }
+
+    // 入力を盤面に反映する。プレイ中とリプレイの再生で共通
+    fn _play(&mut self, input: &Input) {
+        self.renderer.clear_hint(&self.msweeper);
+        self.clicks.count(&input.action);
+        if input.action == Action::Reset {
+            self._reset();
+            return;
+        }
+        let changes = input.apply(&mut self.msweeper);
+        // 時間は最初に開いたときから計る
+        if matches!(input.action, Action::Open | Action::Chord)
+            && self.msweeper.is_started()
+            && !self.msweeper.is_finished()
+        {
+            self.timer.start();
+        }
+        self._apply_changes(&changes);
+    }
 
-        if _is_mine || self.msweeper.is_clear() {
-            self.is_game_end = true;
+    fn _reset(&mut self) {
+        self.is_game_end = false;
+        self.msweeper.clean();
+        if let Some(start_board) = &self.start_board {
+            // 読み込んだ盤面は同じ盤面でやり直す
+            if let Ok(spaces) = board::parse(&start_board.join("\n")) {
+                let _ = self.msweeper.set_board(&spaces);
+            }
+        } else if let Some(playback) = &self.playback {
+            // 再生中は同じ盤面でやり直す
+            let _ = self
+                .msweeper
+                .set_mine_layout(&playback.replay().mine_layout());
         }
+        self.inputs.clear();
+        self.record_start = Instant::now();
+        self.timer.reset();
+        self.clicks = Clicks::default();
+        self.replay_path = None;
+        self.probability_stale = true;
+        self.renderer.redraw(&self.msweeper);
+    }
+
+    fn on_event_leftclick(&mut self) {
+        let Some(p) = self._board_pos() else {
+            return;
+        };
+        self._move_cursor(p.clone());
+        self._open_at(&p);
     }
 
     fn on_event_rightclick(&mut self) {
-        self.msweeper
-            .flag(self.evt_context.pos.x, self.evt_context.pos.y);
+        let Some(p) = self._board_pos() else {
+            return;
+        };
+        self._move_cursor(p.clone());
+        self._flag_at(&p);
+    }
+
+    // 開いたマスなら周りを開く
+    fn _open_at(&mut self, p: &Point<usize>) {
+        let action = if self.msweeper.space(p).state == SpaceState::OPENED {
+            Action::Chord
+        } else {
+            Action::Open
+        };
+        let input = self._record(action, p);
+        self._play(&input);
+    }
+
+    // 開いたマスなら周りに旗を立てる
+    fn _flag_at(&mut self, p: &Point<usize>) {
+        let action = if self.msweeper.space(p).state == SpaceState::OPENED {
+            Action::FlagChord
+        } else {
+            Action::Flag
+        };
+        let input = self._record(action, p);
+        self._play(&input);
+    }
+
+    fn _move_cursor(&mut self, p: Point<usize>) {
+        self.cursor = p;
+        self.renderer
+            .set_cursor(&self.msweeper, Some(self.cursor.clone()));
+    }
+
+    // 盤面の外には出ない
+    fn on_event_move(&mut self, dx: isize, dy: isize) {
+        let x = (self.cursor.x as isize + dx).clamp(0, self.msweeper.width() as isize - 1);
+        let y = (self.cursor.y as isize + dy).clamp(0, self.msweeper.height() as isize - 1);
+        self._move_cursor(Point::<usize> {
+            x: x as usize,
+            y: y as usize,
+        });
+    }
+
+    fn on_event_jump(&mut self, x: Option<usize>, y: Option<usize>) {
+        let p = Point::<usize> {
+            x: x.unwrap_or(self.cursor.x).min(self.msweeper.width() - 1),
+            y: y.unwrap_or(self.cursor.y).min(self.msweeper.height() - 1),
+        };
+        self._move_cursor(p);
+    }
+
+    fn on_event_scroll(&mut self, dx: isize, dy: isize) {
+        self.renderer.scroll_by(&self.msweeper, dx, dy);
+    }
+
+    fn on_event_open(&mut self) {
+        self._open_at(&self.cursor.clone());
+    }
+
+    fn on_event_flag(&mut self) {
+        self._flag_at(&self.cursor.clone());
+    }
+
+    fn on_event_chord(&mut self) {
+        self._chord_at(&self.cursor.clone());
+    }
+
+    fn on_event_chordclick(&mut self) {
+        let Some(p) = self._board_pos() else {
+            return;
+        };
+        self._move_cursor(p.clone());
+        self._chord_at(&p);
+    }
+
+    fn _chord_at(&mut self, p: &Point<usize>) {
+        if self.msweeper.space(p).state != SpaceState::OPENED {
+            return;
+        }
+        let input = self._record(Action::Chord, p);
+        self._play(&input);
     }
 
     fn on_event_enter(&mut self) {
         if self.is_game_end {
-            self.is_game_end = false;
-            self.msweeper.clean();
+            // リセットも記録に含めて保存し直す
+            self._record(Action::Reset, &Point::<usize> { x: 0, y: 0 });
+            self._save_replay();
+            self._reset();
+        }
+    }
+
+    // 取り消しとやり直しは効果があったときだけ記録する
+    fn on_event_undo(&mut self) {
+        self.renderer.clear_hint(&self.msweeper);
+        let changes = self.msweeper.undo();
+        if !changes.is_empty() {
+            self._record(Action::Undo, &Point::<usize> { x: 0, y: 0 });
         }
+        self._apply_changes(&changes);
+    }
+
+    fn on_event_redo(&mut self) {
+        self.renderer.clear_hint(&self.msweeper);
+        let changes = self.msweeper.redo();
+        if !changes.is_empty() {
+            self._record(Action::Redo, &Point::<usize> { x: 0, y: 0 });
+        }
+        self._apply_changes(&changes);
+    }
+
+    fn on_event_hint(&mut self) {
+        if self.is_game_end {
+            return;
+        }
+        if !self.msweeper.is_started() {
+            self.renderer
+                .print_hint(&self.msweeper, None, "Open any cell to start.");
+            return;
+        }
+        let Some(h) = hint::find(&self.msweeper) else {
+            return;
+        };
+        self.msweeper.count_hint();
+        self.renderer
+            .print_hint(&self.msweeper, Some(h.pos), &h.message);
+    }
+
+    // 今の盤面をテキストとJSONで書き出す
+    fn on_event_export(&mut self) {
+        let now = _unix_now();
+        let base =
+            self.data_dir
+                .join("exports")
+                .join(format!("board-{}-{}", now, self.msweeper.seed()));
+        let text_path = base.with_extension("txt");
+        let result = board::export(&self.msweeper, &text_path)
+            .and_then(|_| board::export(&self.msweeper, &base.with_extension("json")));
+        let message = match result {
+            Ok(_) => format!("Exported the board to {} (.json)", text_path.display()),
+            Err(e) => format!("Export failed: {}", e),
+        };
+        self.renderer.print_status(&message);
+    }
+
+    fn on_event_stats(&mut self) {
+        let lines = match stats::load(&stats::stats_path(&self.data_dir)) {
+            Ok(records) => stats::format_table(&stats::summarize(&records)),
+            Err(e) => vec![e],
+        };
+        self.renderer
+            .print_screen("[Statistics] Press any key to return.", &lines);
+        self.screen = true;
+    }
+
+    // 今の盤面の大きさと地雷数の上位の記録を表示する
+    fn on_event_leaderboard(&mut self) {
+        let (width, height, mine_num) = (
+            self.msweeper.width(),
+            self.msweeper.height(),
+            self.msweeper.mine_num(),
+        );
+        let lines = match Leaderboard::load(&Leaderboard::path(&self.data_dir)) {
+            Ok(leaderboard) => match leaderboard.table(width, height, mine_num) {
+                Some(table) => table.format(),
+                None => vec![format!(
+                    "{}x{} {} mines: No times yet.",
+                    width, height, mine_num
+                )],
+            },
+            Err(e) => vec![e],
+        };
+        self.renderer
+            .print_screen("[Best Times] Press any key to return.", &lines);
+        self.screen = true;
+    }
+
+    // すべての操作とキーを表示する
+    fn on_event_help(&mut self) {
+        self.renderer
+            .print_screen("[Keys] Press any key to return.", &self.keys.help());
+        self.screen = true;
+    }
+
+    fn on_event_probability(&mut self) {
+        self.show_probability = !self.show_probability;
+        self.probability_stale = true;
+    }
+
+    // 確率の表示は盤面全体に影響するので、盤面が変わったときだけ計算し直して描き直す
+    fn _update_probability(&mut self) {
+        let probabilities = if self.show_probability && !self.is_game_end {
+            Some(probability::calc(&Solver::from_msweeper(&self.msweeper)))
+        } else {
+            None
+        };
+        self.renderer.set_probabilities(probabilities);
+        self.renderer.print_all_spaces(&self.msweeper);
     }
 
     fn on_event(&mut self) {
+        // 保存できなかった後に別の操作をしたら、次の終了でもう一度保存を試す
+        if !matches!(self.evt_context.event, MSEvent::None | MSEvent::Quit) {
+            self.save_failed = false;
+        }
         match self.evt_context.event {
             MSEvent::Quit => self.on_event_quit(),
             MSEvent::LeftClick => self.on_event_leftclick(),
             MSEvent::RightClick => self.on_event_rightclick(),
+            MSEvent::ChordClick => self.on_event_chordclick(),
+            MSEvent::Move(dx, dy) => self.on_event_move(dx, dy),
+            MSEvent::Jump(x, y) => self.on_event_jump(x, y),
+            MSEvent::Scroll(dx, dy) => self.on_event_scroll(dx, dy),
+            MSEvent::Open => self.on_event_open(),
+            MSEvent::Flag => self.on_event_flag(),
+            MSEvent::Chord => self.on_event_chord(),
             MSEvent::Enter => self.on_event_enter(),
+            MSEvent::Probability => self.on_event_probability(),
+            MSEvent::Hint => self.on_event_hint(),
+            MSEvent::Undo => self.on_event_undo(),
+            MSEvent::Export => self.on_event_export(),
+            MSEvent::Stats => self.on_event_stats(),
+            MSEvent::Leaderboard => self.on_event_leaderboard(),
+            MSEvent::Help => self.on_event_help(),
+            MSEvent::Redo => self.on_event_redo(),
             _ => {}
         }
+        // カーソルの移動やスクロールでは盤面は変わらない
+        if std::mem::take(&mut self.probability_stale)
+            && (self.show_probability || matches!(self.evt_context.event, MSEvent::Probability))
+        {
+            self._update_probability();
+        }
         self.evt_context = EvtContext::construct();
     }
 
+    fn _replay_key_event(&mut self, evt: Event) {
+        let Some(playback) = self.playback.as_mut() else {
+            return;
+        };
+        match evt {
+            Event::Key(Key::Char('q')) | Event::Key(Key::Ctrl('c')) => self.loop_flag = false,
+            Event::Key(Key::Char(' ')) => playback.toggle_pause(),
+            Event::Key(Key::Char('+')) => playback.faster(),
+            Event::Key(Key::Char('-')) => playback.slower(),
+            Event::Key(Key::Char('n')) => {
+                if let Some(input) = playback.step() {
+                    self._play(&input);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn _print_replay_status(&mut self) {
+        let Some(playback) = self.playback.as_ref() else {
+            return;
+        };
+        let status = playback.status();
+        if status != self.status {
+            self.renderer.print_status(&status);
+            self.status = status;
+        }
+    }
+
+    // 端末からの入力を別スレッドで読み、待っている間も時間を進められるようにする
+    fn _spawn_input() -> Receiver<Event> {
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || {
+            let stdin = std::io::stdin();
+            for c in stdin.events() {
+                let Ok(evt) = c else {
+                    break;
+                };
+                if tx.send(evt).is_err() {
+                    break;
+                }
+            }
+        });
+        return rx;
+    }
+
+    pub fn replay_loop(&mut self) {
+        let rx = Self::_spawn_input();
+        loop {
+            match rx.recv_timeout(Duration::from_millis(Self::REPLAY_TICK_MS)) {
+                Ok(evt) => self._replay_key_event(evt),
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => break,
+            }
+            if !self.loop_flag {
+                break;
+            }
+            let inputs = match self.playback.as_mut() {
+                Some(playback) => playback.tick(),
+                None => Vec::new(),
+            };
+            for input in &inputs {
+                self._play(input);
+            }
+            if !inputs.is_empty() {
+                // 盤面を描き直すと表示が消えるので出し直す
+                self.status.clear();
+            }
+            self._print_replay_status();
+            self.renderer.set_elapsed(self.timer.elapsed());
+            self.renderer.flush(&self.msweeper);
+        }
+    }
+
+    // 時計が動いている間は次の秒の変わり目まで、止まっていれば入力があるまで待つ
+    fn _next_tick(&self) -> Option<Duration> {
+        if !self.timer.is_running() {
+            return None;
+        }
+        let ms = self.timer.elapsed().as_millis() as u64 % Self::CLOCK_TICK_MS;
+        return Some(Duration::from_millis(Self::CLOCK_TICK_MS - ms));
+    }
+
     pub fn main_loop(&mut self) {
-        let stdin = std::io::stdin();
-        // 入力処理
-        for c in stdin.events() {
-            self.key_event(c.unwrap());
-            self.on_event();
-            if self.loop_flag == false {
+        let rx = Self::_spawn_input();
+        // リプレイの再生中はカーソルを出さない
+        self.renderer
+            .set_cursor(&self.msweeper, Some(self.cursor.clone()));
+        self.renderer.flush(&self.msweeper);
+        loop {
+            let received = match self._next_tick() {
+                Some(timeout) => rx.recv_timeout(timeout),
+                None => rx.recv().map_err(|_| RecvTimeoutError::Disconnected),
+            };
+            // 入力処理
+            match received {
+                Ok(evt) => {
+                    self.key_event(evt);
+                    self.on_event();
+                }
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => break,
+            }
+            if !self.loop_flag {
                 break;
             }
-            self.msweeper.flush();
+            if !self.screen {
+                self.renderer.set_elapsed(self.timer.elapsed());
+                self.renderer.flush(&self.msweeper);
+            }
         }
     }
 }
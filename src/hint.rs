@@ -0,0 +1,259 @@
+use crate::msweeper::Msweeper;
+use crate::point::Point;
+use crate::probability;
+use crate::solver::{Cell, Reason, Solver};
+
+// 次に開くべきマスとその理由
+pub struct Hint {
+    pub pos: Point<usize>,
+    // 確実に安全なマスならtrue、推測が必要なら一番安全そうなマスを示してfalse
+    pub safe: bool,
+    pub message: String,
+}
+
+// 座標はプレイヤー向けに1始まりで表示する
+fn _fmt_pos(p: &Point<usize>) -> String {
+    return format!("({},{})", p.x + 1, p.y + 1);
+}
+
+fn _fmt_number(solver: &Solver, p: &Point<usize>) -> String {
+    let num = match solver.cell(p) {
+        Cell::Number(n) => *n,
+        _ => 0,
+    };
+    return format!("the {} at {}", num, _fmt_pos(p));
+}
+
+fn _known_mines(solver: &Solver, p: &Point<usize>) -> usize {
+    let mut rv = 0;
+    for pos8 in Point::pos8_iter() {
+        let n = p.utoi().get_pos_8(pos8);
+        if (n.x < 0)
+            || (n.y < 0)
+            || (n.x as usize >= solver.width())
+            || (n.y as usize >= solver.height())
+        {
+            continue;
+        }
+        if *solver.cell(&n.itou()) == Cell::Mine {
+            rv += 1;
+        }
+    }
+    return rv;
+}
+
+fn _explain(solver: &Solver, reason: &Reason) -> String {
+    match reason {
+        Reason::Single(at) => {
+            let flags = _known_mines(solver, at);
+            if flags == 1 {
+                return format!("{} already touches its flag", _fmt_number(solver, at));
+            }
+            return format!(
+                "{} already touches its {} flags",
+                _fmt_number(solver, at),
+                flags
+            );
+        }
+        Reason::Pair(a, b) => {
+            return format!(
+                "{} has all its mines in the cells it shares with {}",
+                _fmt_number(solver, a),
+                _fmt_number(solver, b)
+            );
+        }
+        Reason::MineCount => {
+            return format!("all {} mines are already flagged", solver.mine_num());
+        }
+    }
+}
+
+fn _fmt_flags(flags: usize) -> String {
+    if flags == 1 {
+        return "1 flag".to_string();
+    }
+    return format!("{} flags", flags);
+}
+
+// 旗は地雷として扱うので、数字と合わない旗があればヒントの代わりにそれを示す
+fn _check_flags(solver: &Solver) -> Option<Hint> {
+    let mut flags = Vec::new();
+    for y in 0..solver.height() {
+        for x in 0..solver.width() {
+            let p = Point::<usize> { x, y };
+            match solver.cell(&p) {
+                Cell::Mine => flags.push(p),
+                Cell::Number(n) => {
+                    let touching = _known_mines(solver, &p);
+                    if touching > *n {
+                        return Some(Hint {
+                            message: format!(
+                                "{} touches {}, so a flag is wrong. Fix the flags first.",
+                                _fmt_number(solver, &p),
+                                _fmt_flags(touching)
+                            ),
+                            pos: p,
+                            safe: false,
+                        });
+                    }
+                }
+                Cell::Unknown => {}
+            }
+        }
+    }
+    if flags.len() > solver.mine_num() {
+        return Some(Hint {
+            message: format!(
+                "There are {} but only {} mines, so a flag is wrong. Fix the flags first.",
+                _fmt_flags(flags.len()),
+                solver.mine_num()
+            ),
+            pos: flags.swap_remove(0),
+            safe: false,
+        });
+    }
+    return None;
+}
+
+// 見えている情報から確実に安全なマスを1つ選んで理由を付ける。
+// なければ地雷である確率が一番低いマスを示す。旗は正しいものとして扱う
+pub fn find(ms: &Msweeper) -> Option<Hint> {
+    let solver = Solver::from_msweeper(ms);
+    if let Some(hint) = _check_flags(&solver) {
+        return Some(hint);
+    }
+    let mut hint = _find(&solver)?;
+    if ms.flag_num() > 0 {
+        // 数字と合っていても間違った旗はあり得る
+        hint.message += " This assumes your flags are correct.";
+    }
+    return Some(hint);
+}
+
+fn _find(solver: &Solver) -> Option<Hint> {
+    let deductions = solver.deduce();
+    if let Some(d) = deductions.safe.first() {
+        return Some(Hint {
+            pos: d.pos.clone(),
+            safe: true,
+            message: format!(
+                "{} is safe: {}.",
+                _fmt_pos(&d.pos),
+                _explain(solver, &d.reason)
+            ),
+        });
+    }
+
+    let probabilities = probability::calc(solver);
+    let mut best: Option<(Point<usize>, f64)> = None;
+    for y in 0..solver.height() {
+        for x in 0..solver.width() {
+            let p = Point::<usize> { x, y };
+            let Some(prob) = probabilities.get(&p) else {
+                continue;
+            };
+            match &best {
+                Some((_, b)) if *b <= prob => {}
+                _ => best = Some((p, prob)),
+            }
+        }
+    }
+    let (pos, prob) = best?;
+    if prob <= 0.0 {
+        return Some(Hint {
+            message: format!(
+                "{} is safe: no layout consistent with the numbers puts a mine there.",
+                _fmt_pos(&pos)
+            ),
+            pos,
+            safe: true,
+        });
+    }
+    if !probabilities.is_exact() {
+        return Some(Hint {
+            message: format!(
+                "No safe cell. Too many layouts to count exactly; {} is a guess at about {:.1}%, the density of the remaining mines.",
+                _fmt_pos(&pos),
+                prob * 100.0
+            ),
+            pos,
+            safe: false,
+        });
+    }
+    return Some(Hint {
+        message: format!(
+            "No safe cell. Lowest risk guess is {} with {:.1}% mine probability.",
+            _fmt_pos(&pos),
+            prob * 100.0
+        ),
+        pos,
+        safe: false,
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn safe_cell_is_explained_by_a_number() {
+        let ms = Msweeper::from_board("F1..\n11..\n....\n...*\n");
+        let h = find(&ms).unwrap();
+        assert!(h.safe);
+        assert_eq!(
+            h.message,
+            "(3,1) is safe: the 1 at (2,1) already touches its flag. This assumes your flags are correct."
+        );
+    }
+
+    #[test]
+    fn lowest_risk_guess_when_nothing_is_safe() {
+        let ms = Msweeper::from_board("1.\n*.\n");
+        let h = find(&ms).unwrap();
+        assert!(!h.safe);
+        assert_eq!((h.pos.x, h.pos.y), (1, 0));
+        assert_eq!(
+            h.message,
+            "No safe cell. Lowest risk guess is (2,1) with 33.3% mine probability."
+        );
+    }
+
+    #[test]
+    fn wrong_flag_that_fits_the_numbers_is_assumed_correct() {
+        // 左上の旗は間違いで、1の地雷は右上にある
+        let ms = Msweeper::from_board("f1*\n...\n");
+        let h = find(&ms).unwrap();
+        assert_eq!(
+            h.message,
+            "(3,1) is safe: the 1 at (2,1) already touches its flag. This assumes your flags are correct."
+        );
+    }
+
+    #[test]
+    fn flag_next_to_a_smaller_number_stops_the_hint() {
+        let ms = Msweeper::from_board("f0.\n...\n..*\n");
+        let h = find(&ms).unwrap();
+        assert!(!h.safe);
+        assert_eq!((h.pos.x, h.pos.y), (1, 0));
+        assert_eq!(
+            h.message,
+            "the 0 at (2,1) touches 1 flag, so a flag is wrong. Fix the flags first."
+        );
+    }
+
+    #[test]
+    fn long_frontier_guess_uses_the_density() {
+        // 数字の並びの上下68マスは、配置を数えると探索の上限を超える
+        let ms = Msweeper::from_board(concat!(
+            ".*.**..*....**.*...**.****.***..*.\n",
+            "oooooooooooooooooooooooooooooooooo\n",
+            "..*.*....*..*.**..*...........**.*\n",
+        ));
+        let h = find(&ms).unwrap();
+        assert!(!h.safe);
+        assert_eq!(
+            h.message,
+            "No safe cell. Too many layouts to count exactly; (1,1) is a guess at about 39.7%, the density of the remaining mines."
+        );
+    }
+}
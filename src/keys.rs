@@ -0,0 +1,391 @@
+use serde::de::{self, Deserializer, Visitor};
+use serde::ser::{self, Serializer};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+use termion::event::Key;
+
+// 設定ファイルで指定するキー。1文字か"enter"、"space"、"esc"、"ctrl-r"のように書く
+#[derive(Clone, Copy, PartialEq)]
+pub struct KeyBinding(pub Key);
+
+impl KeyBinding {
+    pub fn name(&self) -> String {
+        match self.0 {
+            Key::Char('\n') => return "'Enter'".to_string(),
+            Key::Char(' ') => return "Space".to_string(),
+            Key::Esc => return "Esc".to_string(),
+            Key::Ctrl(c) => return format!("Ctrl-{}", c),
+            Key::Char(c) if c.is_ascii_digit() => return c.to_string(),
+            Key::Char(c) => return format!("'{}'", c),
+            _ => return "?".to_string(),
+        }
+    }
+}
+
+struct KeyVisitor;
+
+impl<'de> Visitor<'de> for KeyVisitor {
+    type Value = KeyBinding;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "a single character, \"enter\", \"space\", \"esc\" or \"ctrl-<character>\""
+        )
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<KeyBinding, E> {
+        let mut chars = v.chars();
+        let key = match (v, chars.next(), chars.next()) {
+            ("enter", _, _) => Key::Char('\n'),
+            ("space", _, _) => Key::Char(' '),
+            ("esc", _, _) => Key::Esc,
+            (_, Some(c), None) if !c.is_control() => Key::Char(c),
+            _ => match v
+                .strip_prefix("ctrl-")
+                .map(|c| c.chars().collect::<Vec<_>>())
+            {
+                Some(c) if (c.len() == 1) && c[0].is_ascii_alphabetic() => {
+                    Key::Ctrl(c[0].to_ascii_lowercase())
+                }
+                _ => return Err(E::invalid_value(de::Unexpected::Str(v), &self)),
+            },
+        };
+        return Ok(KeyBinding(key));
+    }
+}
+
+impl<'de> Deserialize<'de> for KeyBinding {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<KeyBinding, D::Error> {
+        return deserializer.deserialize_str(KeyVisitor);
+    }
+}
+
+// 設定ファイルと同じ書き方に戻す。並びの既定値に[keys]を重ねるときに使う
+impl Serialize for KeyBinding {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let text = match self.0 {
+            Key::Char('\n') => "enter".to_string(),
+            Key::Char(' ') => "space".to_string(),
+            Key::Esc => "esc".to_string(),
+            Key::Ctrl(c) => format!("ctrl-{}", c),
+            Key::Char(c) => c.to_string(),
+            _ => {
+                return Err(ser::Error::custom(
+                    "this key cannot be written in the config",
+                ))
+            }
+        };
+        return serializer.serialize_str(&text);
+    }
+}
+
+// 移動キーの並び。wasdは左手で動かしてSpaceで開く。viはhjklで動かし、hの代わりにiでヒントを出す
+#[derive(Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Layout {
+    Wasd,
+    Vi,
+}
+
+impl FromStr for Layout {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Layout, String> {
+        match s {
+            "wasd" => Ok(Layout::Wasd),
+            "vi" => Ok(Layout::Vi),
+            _ => Err(format!("Unknown key layout '{}'. (wasd, vi)", s)),
+        }
+    }
+}
+
+// 操作ごとのキー。設定ファイルの[keys]で書いたものだけを並びの既定値から置き換える
+// 移動と開くキーは["h", "a"]のように複数書ける。矢印キーなどはいつでも使える
+#[derive(Clone, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Keys {
+    pub left: Vec<KeyBinding>,
+    pub down: Vec<KeyBinding>,
+    pub up: Vec<KeyBinding>,
+    pub right: Vec<KeyBinding>,
+    pub first_column: KeyBinding,
+    pub last_column: KeyBinding,
+    pub first_row: KeyBinding,
+    pub last_row: KeyBinding,
+    pub scroll_left: KeyBinding,
+    pub scroll_down: KeyBinding,
+    pub scroll_up: KeyBinding,
+    pub scroll_right: KeyBinding,
+    pub open: Vec<KeyBinding>,
+    pub flag: KeyBinding,
+    pub chord: KeyBinding,
+    pub quit: KeyBinding,
+    pub reset: KeyBinding,
+    pub probability: KeyBinding,
+    pub hint: KeyBinding,
+    pub undo: KeyBinding,
+    pub redo: KeyBinding,
+    pub export: KeyBinding,
+    pub stats: KeyBinding,
+    pub leaderboard: KeyBinding,
+    pub help: KeyBinding,
+}
+
+impl Default for Keys {
+    fn default() -> Keys {
+        return Keys::layout(Layout::Wasd);
+    }
+}
+
+impl Keys {
+    pub fn layout(layout: Layout) -> Keys {
+        let char = |c| KeyBinding(Key::Char(c));
+        let mut keys = Keys {
+            left: vec![char('a')],
+            down: vec![char('s')],
+            up: vec![char('w')],
+            right: vec![char('d')],
+            first_column: char('0'),
+            last_column: char('$'),
+            first_row: char('g'),
+            last_row: char('G'),
+            scroll_left: char('A'),
+            scroll_down: char('S'),
+            scroll_up: char('W'),
+            scroll_right: char('D'),
+            open: vec![char(' ')],
+            flag: char('f'),
+            chord: char('c'),
+            quit: char('q'),
+            reset: char('\n'),
+            probability: char('p'),
+            hint: char('h'),
+            undo: char('u'),
+            redo: KeyBinding(Key::Ctrl('r')),
+            export: char('e'),
+            stats: char('t'),
+            leaderboard: char('b'),
+            help: char('?'),
+        };
+        if let Layout::Vi = layout {
+            keys.left = vec![char('h')];
+            keys.down = vec![char('j')];
+            keys.up = vec![char('k')];
+            keys.right = vec![char('l')];
+            keys.scroll_left = char('H');
+            keys.scroll_down = char('J');
+            keys.scroll_up = char('K');
+            keys.scroll_right = char('L');
+            keys.open = vec![char(' '), char('d')];
+            keys.hint = char('i');
+        }
+        return keys;
+    }
+
+    // 並びの既定値に設定ファイルの[keys]を重ねる。引数の並びは[keys]のlayoutより優先する
+    pub fn from_table(table: &toml::Table, layout: Option<Layout>) -> Result<Keys, String> {
+        let mut table = table.clone();
+        let file_layout = match table.remove("layout") {
+            Some(value) => Some(
+                value
+                    .try_into::<Layout>()
+                    .map_err(|e| format!("keys.layout: {}", e))?,
+            ),
+            None => None,
+        };
+        let base = Keys::layout(layout.or(file_layout).unwrap_or(Layout::Wasd));
+        let mut merged = toml::Table::try_from(base).map_err(|e| e.to_string())?;
+        merged.extend(table);
+        let keys: Keys = merged.try_into().map_err(|e| format!("keys: {}", e))?;
+        keys.check()?;
+        return Ok(keys);
+    }
+
+    fn _list(&self) -> Vec<(&'static str, KeyBinding)> {
+        let mut rv = Vec::new();
+        for (name, keys) in [
+            ("left", &self.left),
+            ("down", &self.down),
+            ("up", &self.up),
+            ("right", &self.right),
+            ("open", &self.open),
+        ] {
+            rv.extend(keys.iter().map(|k| (name, *k)));
+        }
+        rv.extend([
+            ("first_column", self.first_column),
+            ("last_column", self.last_column),
+            ("first_row", self.first_row),
+            ("last_row", self.last_row),
+            ("scroll_left", self.scroll_left),
+            ("scroll_down", self.scroll_down),
+            ("scroll_up", self.scroll_up),
+            ("scroll_right", self.scroll_right),
+            ("flag", self.flag),
+            ("chord", self.chord),
+            ("quit", self.quit),
+            ("reset", self.reset),
+            ("probability", self.probability),
+            ("hint", self.hint),
+            ("undo", self.undo),
+            ("redo", self.redo),
+            ("export", self.export),
+            ("stats", self.stats),
+            ("leaderboard", self.leaderboard),
+            ("help", self.help),
+        ]);
+        return rv;
+    }
+
+    // 同じキーを2つの操作に割り当てていればエラー。1から9は移動の回数に使う
+    pub fn check(&self) -> Result<(), String> {
+        let list = self._list();
+        for (i, (name, key)) in list.iter().enumerate() {
+            if let Key::Char('1'..='9') = key.0 {
+                return Err(format!(
+                    "keys.{} cannot be bound to {}; digits 1-9 are move counts",
+                    name,
+                    key.name()
+                ));
+            }
+            if let Some((other, _)) = list[..i].iter().find(|(_, k)| k == key) {
+                return Err(format!(
+                    "keys.{} and keys.{} are both bound to {}",
+                    other,
+                    name,
+                    key.name()
+                ));
+            }
+        }
+        return Ok(());
+    }
+
+    fn _names(keys: &[KeyBinding]) -> String {
+        return keys.iter().map(|k| k.name()).collect::<Vec<_>>().join("/");
+    }
+
+    // 操作説明の行。すべてのキーはヘルプの画面に出す
+    pub fn controls(&self) -> String {
+        return format!(
+            "Press {} to quit, {} for all keys. Click or {} to open, right click or {} to flag.",
+            self.quit.name(),
+            self.help.name(),
+            Self::_names(&self.open),
+            self.flag.name()
+        );
+    }
+
+    // ヘルプの画面に出す操作とキーの一覧
+    pub fn help(&self) -> Vec<String> {
+        let rows = [
+            ("Open", format!("Click or {}", Self::_names(&self.open))),
+            ("Flag", format!("Right click or {}", self.flag.name())),
+            (
+                "Chord",
+                format!("Both buttons, middle click or {}", self.chord.name()),
+            ),
+            (
+                "Move",
+                format!(
+                    "Arrows or {} {} {} {}. A number first repeats the move",
+                    Self::_names(&self.left),
+                    Self::_names(&self.down),
+                    Self::_names(&self.up),
+                    Self::_names(&self.right)
+                ),
+            ),
+            (
+                "Column edge",
+                format!(
+                    "{} {} or Home End",
+                    self.first_column.name(),
+                    self.last_column.name()
+                ),
+            ),
+            (
+                "Row edge",
+                format!(
+                    "{} {} or PgUp PgDn. A number first picks the row",
+                    self.first_row.name(),
+                    self.last_row.name()
+                ),
+            ),
+            (
+                "Scroll",
+                format!(
+                    "Shift+arrows or {} {} {} {}",
+                    self.scroll_left.name(),
+                    self.scroll_down.name(),
+                    self.scroll_up.name(),
+                    self.scroll_right.name()
+                ),
+            ),
+            ("Probability", self.probability.name()),
+            ("Hint", self.hint.name()),
+            (
+                "Undo/redo",
+                format!("{} {}", self.undo.name(), self.redo.name()),
+            ),
+            ("Export", self.export.name()),
+            ("Statistics", self.stats.name()),
+            ("Best times", self.leaderboard.name()),
+            ("Reset", self.reset.name()),
+            ("Quit", self.quit.name()),
+        ];
+        let mut lines: Vec<String> = rows
+            .iter()
+            .map(|(action, keys)| format!("{:<12} {}", action, keys))
+            .collect();
+        lines.push(String::new());
+        // 以前はdでも開けたので、移動に使っているときはそう断っておく
+        if self.right.contains(&KeyBinding(Key::Char('d'))) {
+            lines.push(format!(
+                "d moves right in this layout and does not open; open with {}.",
+                Self::_names(&self.open)
+            ));
+        }
+        lines.push(
+            "Use --keys vi or layout = \"vi\" under [keys] for hjkl; d opens there.".to_string(),
+        );
+        return lines;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn both_layouts_bind_every_key_once() {
+        for layout in [Layout::Wasd, Layout::Vi] {
+            Keys::layout(layout).check().unwrap();
+        }
+        let keys = Keys::default();
+        assert!(keys.right.contains(&KeyBinding(Key::Char('d'))));
+        assert!(keys.hint == KeyBinding(Key::Char('h')));
+        assert!(keys.help().iter().any(|l| l.starts_with("d moves right")));
+        let keys = Keys::layout(Layout::Vi);
+        assert!(keys.open.contains(&KeyBinding(Key::Char('d'))));
+        assert!(!keys.help().iter().any(|l| l.starts_with("d moves right")));
+    }
+
+    #[test]
+    fn config_keys_are_laid_over_the_layout() {
+        let table: toml::Table = toml::from_str("layout = \"vi\"\nflag = \"m\"\n").unwrap();
+        let keys = Keys::from_table(&table, None).unwrap();
+        assert!(keys.left.contains(&KeyBinding(Key::Char('h'))));
+        assert!(keys.flag == KeyBinding(Key::Char('m')));
+
+        let keys = Keys::from_table(&table, Some(Layout::Wasd)).unwrap();
+        assert!(keys.left.contains(&KeyBinding(Key::Char('a'))));
+        assert!(keys.flag == KeyBinding(Key::Char('m')));
+
+        let table: toml::Table = toml::from_str("flag = \"a\"\n").unwrap();
+        assert_eq!(
+            Keys::from_table(&table, None).err().unwrap(),
+            "keys.left and keys.flag are both bound to 'a'"
+        );
+    }
+}
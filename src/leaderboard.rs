@@ -0,0 +1,190 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Entry {
+    pub name: String,
+    pub time_ms: u64,
+    // 記録した日時(UNIX時間の秒)
+    pub date: u64,
+    pub seed: u64,
+}
+
+// 盤面の大きさと地雷数ごとの速い順の記録
+#[derive(Serialize, Deserialize)]
+pub struct Table {
+    pub width: usize,
+    pub height: usize,
+    pub mine_num: usize,
+    pub entries: Vec<Entry>,
+}
+
+impl Table {
+    pub fn is_for(&self, width: usize, height: usize, mine_num: usize) -> bool {
+        return (self.width, self.height, self.mine_num) == (width, height, mine_num);
+    }
+
+    pub fn format(&self) -> Vec<String> {
+        let mut rv = vec![format!(
+            "{}x{} {} mines",
+            self.width, self.height, self.mine_num
+        )];
+        if self.entries.is_empty() {
+            rv.push("  No times yet.".to_string());
+        }
+        for (i, e) in self.entries.iter().enumerate() {
+            rv.push(format!(
+                "{:>4}. {:<16} {:>9.3}s",
+                i + 1,
+                e.name,
+                e.time_ms as f64 / 1000.0
+            ));
+        }
+        return rv;
+    }
+}
+
+#[derive(Default, Serialize, Deserialize)]
+pub struct Leaderboard {
+    pub tables: Vec<Table>,
+}
+
+impl Leaderboard {
+    pub const SIZE: usize = 10;
+    pub const NAME_MAX_LEN: usize = 16;
+
+    pub fn path(data_dir: &Path) -> PathBuf {
+        return data_dir.join("leaderboard.json");
+    }
+
+    // まだ記録がなければ空
+    pub fn load(path: &Path) -> Result<Leaderboard, String> {
+        if !path.exists() {
+            return Ok(Leaderboard::default());
+        }
+        let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let board: Leaderboard =
+            serde_json::from_str(&text).map_err(|e| format!("{}: {}", path.display(), e))?;
+        return Ok(board);
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).map_err(|e| format!("{}: {}", dir.display(), e))?;
+        }
+        let text = serde_json::to_string_pretty(self).map_err(|e| e.to_string())?;
+        fs::write(path, text).map_err(|e| format!("{}: {}", path.display(), e))?;
+        return Ok(());
+    }
+
+    pub fn table(&self, width: usize, height: usize, mine_num: usize) -> Option<&Table> {
+        return self
+            .tables
+            .iter()
+            .find(|t| t.is_for(width, height, mine_num));
+    }
+
+    // 上位に入る時間ならその順位(0始まり)
+    pub fn rank(
+        &self,
+        width: usize,
+        height: usize,
+        mine_num: usize,
+        time_ms: u64,
+    ) -> Option<usize> {
+        let entries = match self.table(width, height, mine_num) {
+            Some(t) => &t.entries[..],
+            None => &[],
+        };
+        // 同じ時間なら先に出した記録を上にする
+        let rank = entries.iter().filter(|e| e.time_ms <= time_ms).count();
+        if rank >= Self::SIZE {
+            return None;
+        }
+        return Some(rank);
+    }
+
+    pub fn insert(
+        &mut self,
+        width: usize,
+        height: usize,
+        mine_num: usize,
+        entry: Entry,
+    ) -> Option<usize> {
+        let rank = self.rank(width, height, mine_num, entry.time_ms)?;
+        let index = match self
+            .tables
+            .iter()
+            .position(|t| t.is_for(width, height, mine_num))
+        {
+            Some(index) => index,
+            None => {
+                self.tables.push(Table {
+                    width,
+                    height,
+                    mine_num,
+                    entries: Vec::new(),
+                });
+                self.tables
+                    .sort_by_key(|t| (t.width * t.height, t.mine_num, t.width));
+                self.tables
+                    .iter()
+                    .position(|t| t.is_for(width, height, mine_num))
+                    .unwrap()
+            }
+        };
+        let entries = &mut self.tables[index].entries;
+        entries.insert(rank, entry);
+        entries.truncate(Self::SIZE);
+        return Some(rank);
+    }
+
+    // すべての盤面の表を続けて並べる
+    pub fn format_all(&self) -> Vec<String> {
+        if self.tables.is_empty() {
+            return vec!["No times recorded yet.".to_string()];
+        }
+        let mut rv = Vec::new();
+        for t in &self.tables {
+            if !rv.is_empty() {
+                rv.push(String::new());
+            }
+            rv.extend(t.format());
+        }
+        return rv;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(name: &str, time_ms: u64) -> Entry {
+        return Entry {
+            name: name.to_string(),
+            time_ms,
+            date: 0,
+            seed: 0,
+        };
+    }
+
+    #[test]
+    fn keeps_the_ten_fastest_times() {
+        let mut board = Leaderboard::default();
+        for i in 0..Leaderboard::SIZE as u64 {
+            assert_eq!(
+                board.insert(9, 9, 10, entry("a", 1000 + i * 100)),
+                Some(i as usize)
+            );
+        }
+        assert_eq!(board.rank(9, 9, 10, 5000), None);
+        assert_eq!(board.insert(9, 9, 10, entry("b", 1000)), Some(1));
+        assert_eq!(board.rank(16, 16, 40, 99999), Some(0));
+
+        let entries = &board.table(9, 9, 10).unwrap().entries;
+        assert_eq!(entries.len(), Leaderboard::SIZE);
+        assert_eq!(entries[1].name, "b");
+        assert_eq!(entries.last().unwrap().time_ms, 1800);
+    }
+}
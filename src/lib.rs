@@ -0,0 +1,17 @@
+#![allow(
+    clippy::needless_return,
+    clippy::needless_range_loop,
+    clippy::upper_case_acronyms
+)]
+
+pub mod board;
+pub mod hint;
+pub mod leaderboard;
+pub mod metrics;
+pub mod msweeper;
+pub mod point;
+pub mod probability;
+pub mod replay;
+pub mod save;
+pub mod solver;
+pub mod stats;
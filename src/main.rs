@@ -1,15 +1,53 @@
+#![allow(
+    clippy::needless_return,
+    clippy::needless_range_loop,
+    clippy::upper_case_acronyms
+)]
+
 mod config;
+mod config_file;
 mod game;
-mod msweeper;
-mod point;
+mod keys;
+mod playback;
+mod preset;
+mod renderer;
 mod terminal;
+mod theme;
+mod timer;
 
 use config::Config;
 use game::Game;
-use msweeper::Msweeper;
+use msweeper_rs::board;
+use msweeper_rs::leaderboard::Leaderboard;
+use msweeper_rs::msweeper;
+use msweeper_rs::msweeper::Msweeper;
+use msweeper_rs::point;
+use msweeper_rs::replay::Replay;
+use msweeper_rs::save::{self, SaveData};
+use msweeper_rs::stats;
 use std::env;
+use std::path::Path;
 use std::process;
-use terminal::Terminal;
+
+// 端末を使わずに盤面を書き出す
+fn export_board(cfg: &Config, path: &Path) -> Result<(), String> {
+    let ms = if let Some(board_path) = &cfg.board {
+        let spaces = board::load(board_path)?;
+        let mut ms = Msweeper::construct(spaces[0].len(), spaces.len(), 0, cfg.seed)?;
+        ms.set_board(&spaces)?;
+        ms
+    } else if cfg.resume {
+        let slot_path = save::slot_path(&cfg.data_dir.join("saves"), &cfg.slot)?;
+        Msweeper::from_state(SaveData::load(&slot_path)?.msweeper)?
+    } else {
+        Msweeper::construct(cfg.width, cfg.height, cfg.mine_num, cfg.seed)?
+    };
+    if path == Path::new("-") {
+        print!("{}", board::to_text(&ms));
+        return Ok(());
+    }
+    return board::export(&ms, path);
+}
 
 fn main() {
     let args: Vec<String> = env::args().collect();
@@ -18,7 +56,90 @@ fn main() {
         Err(_) => process::exit(1),
     };
 
-    let mut game = Game::construct(&cfg);
+    if cfg.stats {
+        match stats::load(&stats::stats_path(&cfg.data_dir)) {
+            Ok(records) => {
+                for line in stats::format_table(&stats::summarize(&records)) {
+                    println!("{}", line);
+                }
+            }
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                process::exit(1);
+            }
+        }
+        return;
+    }
+
+    if cfg.leaderboard {
+        match Leaderboard::load(&Leaderboard::path(&cfg.data_dir)) {
+            Ok(leaderboard) => {
+                for line in leaderboard.format_all() {
+                    println!("{}", line);
+                }
+            }
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                process::exit(1);
+            }
+        }
+        return;
+    }
+
+    if let Some(path) = &cfg.export {
+        if let Err(e) = export_board(&cfg, path) {
+            eprintln!("Error: {}", e);
+            process::exit(1);
+        }
+        return;
+    }
+
+    if let Some(path) = &cfg.replay {
+        // 端末を切り替える前に読み込んで、エラーを表示できるようにする
+        let replay = match Replay::load(path) {
+            Ok(r) => r,
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                process::exit(1);
+            }
+        };
+        let mut game = match Game::construct_replay(&cfg, replay) {
+            Ok(g) => g,
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                process::exit(1);
+            }
+        };
+        game.replay_loop();
+        return;
+    }
+
+    let mut game = if let Some(path) = &cfg.board {
+        let result = board::load(path).and_then(|spaces| Game::construct_board(&cfg, spaces));
+        match result {
+            Ok(g) => g,
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                process::exit(1);
+            }
+        }
+    } else if cfg.resume {
+        match Game::construct_resume(&cfg) {
+            Ok(g) => g,
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                process::exit(1);
+            }
+        }
+    } else {
+        match Game::construct(&cfg) {
+            Ok(g) => g,
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                process::exit(1);
+            }
+        }
+    };
 
     game.main_loop();
 }
@@ -0,0 +1,136 @@
+use crate::msweeper::Msweeper;
+use crate::replay::Action;
+
+// プレイ中のクリック数
+#[derive(Clone, Default)]
+pub struct Clicks {
+    pub left: usize,
+    pub right: usize,
+    // 開いている数字をクリックして周りを開いた回数
+    pub chord: usize,
+    // 開いている数字を右クリックして周りに旗を立てた回数
+    pub flag_chord: usize,
+}
+
+impl Clicks {
+    pub fn count(&mut self, action: &Action) {
+        match action {
+            Action::Open => self.left += 1,
+            Action::Flag => self.right += 1,
+            Action::Chord => self.chord += 1,
+            Action::FlagChord => self.flag_chord += 1,
+            Action::Undo | Action::Redo | Action::Reset => {}
+        }
+    }
+
+    pub fn total(&self) -> usize {
+        return self.left + self.right + self.chord + self.flag_chord;
+    }
+}
+
+// 終わったゲームの効率
+pub struct Metrics {
+    pub bbbv: usize,
+    // 負けたときは開け終わった分だけ
+    pub solved_bbbv: usize,
+    pub time_ms: u64,
+    pub clicks: Clicks,
+}
+
+impl Metrics {
+    pub fn construct(ms: &Msweeper, time_ms: u64, clicks: &Clicks) -> Metrics {
+        return Metrics {
+            bbbv: ms.bbbv(),
+            solved_bbbv: ms.solved_bbbv(),
+            time_ms,
+            clicks: clicks.clone(),
+        };
+    }
+
+    pub fn bbbv_per_sec(&self) -> f64 {
+        if self.time_ms == 0 {
+            return 0.0;
+        }
+        return self.solved_bbbv as f64 * 1000.0 / self.time_ms as f64;
+    }
+
+    // クリック1回あたりに進めた3BV
+    pub fn ioe(&self) -> f64 {
+        if self.clicks.total() == 0 {
+            return 0.0;
+        }
+        return self.solved_bbbv as f64 / self.clicks.total() as f64;
+    }
+
+    pub fn solved_rate(&self) -> f64 {
+        if self.bbbv == 0 {
+            return 0.0;
+        }
+        return self.solved_bbbv as f64 / self.bbbv as f64;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::point::Point;
+
+    fn metrics(bbbv: usize, solved_bbbv: usize, time_ms: u64, left: usize) -> Metrics {
+        return Metrics {
+            bbbv,
+            solved_bbbv,
+            time_ms,
+            clicks: Clicks {
+                left,
+                right: 2,
+                chord: 1,
+                flag_chord: 1,
+            },
+        };
+    }
+
+    fn assert_near(actual: f64, expected: f64) {
+        assert!(
+            (actual - expected).abs() < 1e-9,
+            "actual = {}, expected = {}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn efficiency_formulas() {
+        let m = metrics(30, 30, 12000, 8);
+        assert_eq!(m.clicks.total(), 12);
+        assert_near(m.bbbv_per_sec(), 2.5);
+        assert_near(m.ioe(), 2.5);
+        assert_near(m.solved_rate(), 1.0);
+    }
+
+    #[test]
+    fn zero_time_and_zero_clicks_give_zero() {
+        let mut m = metrics(5, 5, 0, 0);
+        m.clicks = Clicks::default();
+        assert_near(m.bbbv_per_sec(), 0.0);
+        assert_near(m.ioe(), 0.0);
+        assert_near(metrics(0, 0, 1000, 1).solved_rate(), 0.0);
+    }
+
+    #[test]
+    fn loss_counts_only_the_solved_part() {
+        // 地雷の左右に0の領域が1つずつあるので3BVは2
+        let mut ms = Msweeper::from_board("..*..\n");
+        let mut clicks = Clicks::default();
+        ms.open(&Point { x: 0, y: 0 });
+        clicks.count(&Action::Open);
+        ms.open(&Point { x: 2, y: 0 });
+        clicks.count(&Action::Open);
+        assert!(ms.is_finished() && !ms.is_clear());
+
+        let m = Metrics::construct(&ms, 4000, &clicks);
+        assert_eq!((m.bbbv, m.solved_bbbv), (2, 1));
+        assert_near(m.solved_rate(), 0.5);
+        assert_near(m.bbbv_per_sec(), 0.25);
+        assert_near(m.ioe(), 0.5);
+    }
+}
@@ -1,68 +1,153 @@
-extern crate termion;
-
 use crate::point::Point;
-use crate::Terminal;
-use rand::Rng;
-use termion::color;
-/*
-use termion::input::TermRead;
-use termion::event::*;
-use termion::raw::RawTerminal;
-use termion::screen::AlternateScreen;
-use termion::screen::IntoAlternateScreen;
-*/
-
-#[derive(Clone, PartialEq)]
-enum SpaceState {
+use crate::solver::{Cell, Solver};
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub enum SpaceState {
     UNOPENED,
     OPENED,
     FLAG,
+    // ?の印。旗としては数えず、未開封のマスと同じに扱う
+    QUESTION,
 }
 
-#[derive(Clone, PartialEq)]
-enum SpaceType {
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub enum SpaceType {
     EMPTY,
     MINE,
 }
 
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Space {
+    pub state: SpaceState,
+    pub stype: SpaceType,
+}
+
+// 最初に開いたマスの扱い
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum FirstClick {
+    // 最初のマスも地雷になりうる
+    Classic,
+    // 最初のマスだけは地雷にならない
+    SafeCell,
+    // 最初のマスの周囲3x3に地雷を置かず、必ず領域が開く
+    SafeOpening,
+}
+
+impl FromStr for FirstClick {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<FirstClick, String> {
+        match s {
+            "classic" => Ok(FirstClick::Classic),
+            "safe-cell" => Ok(FirstClick::SafeCell),
+            "safe-opening" => Ok(FirstClick::SafeOpening),
+            _ => Err(format!(
+                "Unknown first click policy '{}'. (classic, safe-cell, safe-opening)",
+                s
+            )),
+        }
+    }
+}
+
+// 盤面の変化を表すイベント。描画側はこれを受け取って画面を更新する
 #[derive(Clone)]
-struct Space {
-    state: SpaceState,
-    stype: SpaceType,
+pub enum MSChange {
+    // マスの状態が変化した
+    Space(Point<usize>),
+    // 踏んでしまった地雷
+    Exploded(Point<usize>),
+    GameOver,
+    GameClear,
+    // 取り消しによってゲームの終了が取り消された
+    Resumed,
+    // 推測なしで解ける盤面を作れなかった。地雷は置かずに始める前に戻る
+    NoGuessFailed,
+}
+
+// 途中のゲームを保存して再開するための状態。取り消しの履歴は含めない
+#[derive(Serialize, Deserialize)]
+pub struct MsweeperState {
+    pub mine_num: usize,
+    pub fixed_seed: Option<u64>,
+    pub seed: u64,
+    pub no_guess: bool,
+    pub first_click: FirstClick,
+    pub started: bool,
+    pub opened_num: usize,
+    pub flag_num: usize,
+    pub hint_num: usize,
+    pub practice: bool,
+    pub undo_used: bool,
+    pub board: Vec<Vec<Space>>,
+}
+
+// 取り消しで戻す盤面全体の状態
+#[derive(Clone)]
+struct Counters {
+    opened_num: usize,
+    flag_num: usize,
+    finished: bool,
+}
+
+// 1回の操作で変化した内容。取り消しとやり直しに使う
+struct Step {
+    // (マス, 変化前, 変化後)
+    spaces: Vec<(Point<usize>, SpaceState, SpaceState)>,
+    before: Counters,
+    after: Counters,
+    // マス以外のイベント。やり直しのときにもう一度通知する
+    events: Vec<MSChange>,
 }
 
 pub struct Msweeper {
     mine_num: usize,
-    term: Terminal,
-    board_offset: Point<usize>,
-    remain_offset: Point<usize>,
-    controls_offset: Point<usize>,
-    cmn_msg_offset: Point<usize>,
+    // 指定された場合は毎回同じ盤面になる
+    fixed_seed: Option<u64>,
+    seed: u64,
+    // 推測なしで解ける盤面だけを作る
+    no_guess: bool,
+    // 推測なしで解ける盤面を探すのに使える時間
+    no_guess_time_limit: Duration,
+    first_click: FirstClick,
     started: bool,
+    finished: bool,
 
     opened_num: usize,
     flag_num: usize,
-
-    board: Box<Vec<Vec<Space>>>,
+    // このゲームで使ったヒントの数
+    hint_num: usize,
+    // 練習モードでは地雷を踏んでも取り消せる
+    practice: bool,
+    // 取り消しを使ったゲームは記録に残さない
+    undo_used: bool,
+    // 旗の次に?の印を付ける
+    question_marks: bool,
+    // 地雷を置いたときに数えた3BV
+    bbbv: usize,
+    // 地雷を踏んだときには盤面を全部開くので、その前に開けていた3BV
+    exploded_bbbv: Option<usize>,
+
+    board: Vec<Vec<Space>>,
+    changes: Vec<MSChange>,
+    recording: Vec<(Point<usize>, SpaceState, SpaceState)>,
+    history: Vec<Step>,
+    redo_history: Vec<Step>,
 }
 
 impl Msweeper {
-    const DEFAULT_BOARD_OFFSET: Point<usize> = Point { x: 2, y: 6 };
-    const DEFAULT_CONTROLS_OFFSET: Point<usize> = Point {
-        x: Self::DEFAULT_BOARD_OFFSET.x,
-        y: 1,
-    };
-    const DEFAULT_CMN_MSG_OFFSET: Point<usize> = Point {
-        x: Self::DEFAULT_BOARD_OFFSET.x,
-        y: 2,
-    };
-    const DEFAULT_REMAIN_OFFSET: Point<usize> = Point {
-        x: Self::DEFAULT_BOARD_OFFSET.x,
-        y: 4,
-    };
+    // 最初のクリックで画面が止まるので、この時間で解ける盤面が見つからなければ諦める
+    const NO_GUESS_TIME_LIMIT: Duration = Duration::from_secs(3);
+    // これより地雷の割合が高いと推測なしの盤面はまず作れない
+    pub const NO_GUESS_MAX_DENSITY: f64 = 0.25;
 
     pub fn width(&self) -> usize {
-        if self.height() <= 0 {
+        if self.height() == 0 {
             return 0;
         }
         return self.board[0].len();
@@ -72,103 +157,321 @@ impl Msweeper {
         self.board.len()
     }
 
-    fn _print_message(&mut self, str: &str, p: &Point<usize>) {
-        self.term.color_bg(color::Reset);
-        self.term.color_fg(color::Reset);
-        self.term.clear_line(p.y);
-        self.term.print(str, p.x, p.y);
+    pub fn mine_num(&self) -> usize {
+        self.mine_num
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn set_no_guess(&mut self, no_guess: bool) {
+        self.no_guess = no_guess;
+    }
+
+    pub fn set_first_click(&mut self, first_click: FirstClick) {
+        self.first_click = first_click;
+    }
+
+    pub fn set_practice(&mut self, practice: bool) {
+        self.practice = practice;
+    }
+
+    pub fn set_question_marks(&mut self, question_marks: bool) {
+        self.question_marks = question_marks;
+    }
+
+    pub fn is_question_marks(&self) -> bool {
+        return self.question_marks;
+    }
+
+    pub fn is_practice(&self) -> bool {
+        return self.practice;
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.finished
     }
 
-    fn _print_gameover(&mut self) {
-        self._print_message(
-            &format!(
-                "{}[Game Over]{} Press 'Enter' to reset.",
-                color::Bg(color::Red),
-                color::Bg(color::Reset)
-            ),
-            &self.cmn_msg_offset.clone(),
-        );
+    pub fn is_undo_used(&self) -> bool {
+        self.undo_used
     }
 
-    fn _print_gameclear(&mut self) {
-        self._print_message(
-            &format!(
-                "{}[Game Clear]{} Press 'Enter' to reset.",
-                color::Bg(color::Green),
-                color::Bg(color::Reset)
-            ),
-            &self.cmn_msg_offset.clone(),
-        );
+    pub fn is_started(&self) -> bool {
+        self.started
     }
 
-    fn _print_controls(&mut self) {
-        self._print_message(
-            &format!("Press 'q' to quit, Left click to open, Right click to set flag."),
-            &self.controls_offset.clone(),
-        );
+    pub fn hint_num(&self) -> usize {
+        self.hint_num
     }
 
-    fn _print_remain(&mut self) {
-        self._print_message(
-            &format!(
-                "REMAIN: {}",
-                (self.mine_num as isize) - (self.flag_num as isize)
-            ),
-            &self.remain_offset.clone(),
-        );
+    pub fn count_hint(&mut self) {
+        self.hint_num += 1;
     }
 
-    pub fn flush(&mut self) {
-        self._print_remain();
-        self.term.flush();
+    pub fn remain(&self) -> isize {
+        return (self.mine_num as isize) - (self.flag_num as isize);
     }
 
-    fn _place_mine(&mut self, press_p: &Point<usize>) {
-        let _mine_num = self.mine_num;
-        let mut mine_cnt = 0;
+    pub fn flag_num(&self) -> usize {
+        return self.flag_num;
+    }
+
+    pub fn space(&self, p: &Point<usize>) -> &Space {
+        &self.board[p.y][p.x]
+    }
+
+    // 周囲8マスの地雷数
+    pub fn mine_count(&self, p: &Point<usize>) -> usize {
+        return self._calc_mnum(&p.utoi());
+    }
+
+    fn _is_excluded(excluded: &[Point<usize>], x: usize, y: usize) -> bool {
+        return excluded.iter().any(|p| p.x == x && p.y == y);
+    }
+
+    // 除外したマス以外から地雷の位置を一様に選ぶ(部分的なFisher-Yatesシャッフル)
+    fn _place_mine(&mut self, excluded: &[Point<usize>], rng: &mut ChaCha8Rng) {
+        let mut allowed = Vec::new();
         for _y in 0..(self.board.len()) {
             for _x in 0..(self.board[_y].len()) {
-                if press_p.x == _x && press_p.y == _y {
-                    continue;
+                self.board[_y][_x].stype = SpaceType::EMPTY;
+                if !Self::_is_excluded(excluded, _x, _y) {
+                    allowed.push(Point::<usize> { x: _x, y: _y });
                 }
-                mine_cnt += 1;
-                if mine_cnt > _mine_num {
-                    self.board[_y][_x] = Space {
-                        state: SpaceState::UNOPENED,
-                        stype: SpaceType::EMPTY,
-                    };
+            }
+        }
+        for i in 0..self.mine_num {
+            // usizeの幅に依存しないようにu64で乱数を取る
+            let j = rng.gen_range(i as u64..allowed.len() as u64) as usize;
+            allowed.swap(i, j);
+            let p = &allowed[i];
+            self.board[p.y][p.x].stype = SpaceType::MINE;
+        }
+    }
+
+    // 最初に開くマスの周囲で地雷を置かない範囲
+    fn _safe_zone(&self, p: &Point<usize>) -> Vec<Point<usize>> {
+        // 推測なしの盤面は最初に必ず領域が開く必要がある
+        let first_click = if self.no_guess {
+            FirstClick::SafeOpening
+        } else {
+            self.first_click.clone()
+        };
+        let mut zone = vec![p.clone()];
+        match first_click {
+            FirstClick::Classic => return Vec::new(),
+            FirstClick::SafeCell => return zone,
+            FirstClick::SafeOpening => {}
+        }
+        // 盤面に対して地雷が多すぎる場合は開いたマスだけを除外する
+        for pos8 in Point::pos8_iter() {
+            let n = p.utoi().get_pos_8(pos8);
+            if self._is_inbound(&n) {
+                zone.push(n.itou());
+            }
+        }
+        if self.width() * self.height() - zone.len() < self.mine_num {
+            zone.truncate(1);
+        }
+        return zone;
+    }
+
+    // 地雷の配置。trueが地雷
+    pub fn mine_layout(&self) -> Vec<Vec<bool>> {
+        return self
+            .board
+            .iter()
+            .map(|row| row.iter().map(|sp| sp.stype == SpaceType::MINE).collect())
+            .collect();
+    }
+
+    // 乱数で作る代わりに地雷の配置を直接指定してゲームを始める
+    pub fn set_mine_layout(&mut self, mines: &[Vec<bool>]) -> Result<(), String> {
+        if (mines.len() != self.height()) || mines.iter().any(|row| row.len() != self.width()) {
+            return Err(format!(
+                "The mine layout does not match the board size. width = {}, height = {}",
+                self.width(),
+                self.height()
+            ));
+        }
+        let mine_num = mines.iter().flatten().filter(|&&m| m).count();
+        if mine_num != self.mine_num {
+            return Err(format!(
+                "The mine layout has {} mines, expected {}.",
+                mine_num, self.mine_num
+            ));
+        }
+        for y in 0..self.height() {
+            for x in 0..self.width() {
+                self.board[y][x].stype = if mines[y][x] {
+                    SpaceType::MINE
                 } else {
-                    self.board[_y][_x] = Space {
-                        state: SpaceState::UNOPENED,
-                        stype: SpaceType::MINE,
-                    };
-                }
+                    SpaceType::EMPTY
+                };
             }
         }
+        self.started = true;
+        self.bbbv = self._count_bbbv(false);
+        return Ok(());
     }
 
-    fn _randomize(&mut self, press_p: &Point<usize>) {
-        let mut rng = rand::thread_rng();
-        for _y in 0..(self.board.len()) {
-            for _x in 0..(self.board[_y].len()) {
-                if press_p.x == _x && press_p.y == _y {
+    // 開いたマスや旗も含めて盤面をそのまま置き換える。地雷数は盤面から数える
+    pub fn set_board(&mut self, board: &[Vec<Space>]) -> Result<(), String> {
+        if (board.len() != self.height()) || board.iter().any(|row| row.len() != self.width()) {
+            return Err(format!(
+                "The board does not match the board size. width = {}, height = {}",
+                self.width(),
+                self.height()
+            ));
+        }
+        let spaces = board.iter().flatten();
+        let mine_num = spaces
+            .clone()
+            .filter(|sp| sp.stype == SpaceType::MINE)
+            .count();
+        if mine_num >= self.width() * self.height() {
+            return Err(format!(
+                "The number of mines exceeds board size. width = {}, height = {}, mine_num = {}",
+                self.width(),
+                self.height(),
+                mine_num
+            ));
+        }
+        if spaces
+            .clone()
+            .any(|sp| sp.state == SpaceState::OPENED && sp.stype == SpaceType::MINE)
+        {
+            return Err("The board has an opened mine.".to_string());
+        }
+
+        self.mine_num = mine_num;
+        self.opened_num = spaces
+            .clone()
+            .filter(|sp| sp.state == SpaceState::OPENED)
+            .count();
+        self.flag_num = spaces.filter(|sp| sp.state == SpaceState::FLAG).count();
+        self.board = board.to_vec();
+        self.started = true;
+        self.finished = self.is_clear();
+        self.bbbv = self._count_bbbv(false);
+        return Ok(());
+    }
+
+    // 盤面を開けるのに必要な最小のクリック数
+    pub fn bbbv(&self) -> usize {
+        return self.bbbv;
+    }
+
+    // 3BVのうち開け終わった分
+    pub fn solved_bbbv(&self) -> usize {
+        if let (true, Some(solved)) = (self.finished && self._is_exploded(), self.exploded_bbbv) {
+            return solved;
+        }
+        return self._count_bbbv(true);
+    }
+
+    // 0の領域ごとに1回と、0に接していない数字ごとに1回を数える。
+    // solved_onlyなら開いている分だけ数える
+    fn _count_bbbv(&self, solved_only: bool) -> usize {
+        let mut visited = vec![vec![false; self.width()]; self.height()];
+        let mut rv = 0;
+        for y in 0..self.height() {
+            for x in 0..self.width() {
+                let p = Point::<usize> { x, y };
+                if visited[y][x]
+                    || (self.board[y][x].stype == SpaceType::MINE)
+                    || (self._calc_mnum(&p.utoi()) != 0)
+                {
                     continue;
                 }
-                let press_pos = press_p.y * self.height() + press_p.x;
-                let orig_rand = rng.gen::<usize>() % (self.width() * self.height() - 1);
-                let rand = if orig_rand >= press_pos {
-                    orig_rand + 1
-                } else {
-                    orig_rand
-                };
-                let rand_x = rand % self.height();
-                let rand_y = rand / self.height();
+                // 0の領域とその周りの数字を1回で開ける
+                let mut solved = false;
+                let mut stack = vec![p];
+                visited[y][x] = true;
+                while let Some(q) = stack.pop() {
+                    solved |= self.board[q.y][q.x].state == SpaceState::OPENED;
+                    for pos8 in Point::pos8_iter() {
+                        let n = q.utoi().get_pos_8(pos8);
+                        if !self._is_inbound(&n) {
+                            continue;
+                        }
+                        let n = n.itou();
+                        if visited[n.y][n.x] {
+                            continue;
+                        }
+                        visited[n.y][n.x] = true;
+                        if self._calc_mnum(&n.utoi()) == 0 {
+                            stack.push(n);
+                        }
+                    }
+                }
+                if solved || !solved_only {
+                    rv += 1;
+                }
+            }
+        }
+        for y in 0..self.height() {
+            for x in 0..self.width() {
+                let space = &self.board[y][x];
+                if visited[y][x] || (space.stype == SpaceType::MINE) {
+                    continue;
+                }
+                if (space.state == SpaceState::OPENED) || !solved_only {
+                    rv += 1;
+                }
+            }
+        }
+        return rv;
+    }
+
+    fn _is_exploded(&self) -> bool {
+        return self
+            .board
+            .iter()
+            .flatten()
+            .any(|sp| sp.state == SpaceState::OPENED && sp.stype == SpaceType::MINE);
+    }
 
-                // swap
-                let mut space = self.board[_y][_x].clone();
-                std::mem::swap(&mut self.board[rand_y][rand_x], &mut space);
-                std::mem::swap(&mut self.board[_y][_x], &mut space);
+    // 見えている情報だけで最後まで開けられるかを確かめる
+    fn _is_solvable(&self, start: &Point<usize>) -> bool {
+        let mut solver = Solver::construct(self.width(), self.height(), self.mine_num);
+        let mut opened = vec![vec![false; self.width()]; self.height()];
+        let mut opened_num = 0;
+        let mut stack = vec![start.clone()];
+        loop {
+            while let Some(p) = stack.pop() {
+                if opened[p.y][p.x] {
+                    continue;
+                }
+                if self.board[p.y][p.x].stype == SpaceType::MINE {
+                    return false;
+                }
+                opened[p.y][p.x] = true;
+                opened_num += 1;
+                let mnum = self.mine_count(&p);
+                solver.set(&p, Cell::Number(mnum));
+                if mnum > 0 {
+                    continue;
+                }
+                for pos8 in Point::pos8_iter() {
+                    let n = p.utoi().get_pos_8(pos8);
+                    if self._is_inbound(&n) {
+                        stack.push(n.itou());
+                    }
+                }
             }
+            if opened_num >= self.width() * self.height() - self.mine_num {
+                return true;
+            }
+            let deductions = solver.deduce();
+            if deductions.is_empty() {
+                return false;
+            }
+            for d in &deductions.mines {
+                solver.set(&d.pos, Cell::Mine);
+            }
+            stack = deductions.safe.into_iter().map(|d| d.pos).collect();
         }
     }
 
@@ -183,29 +486,53 @@ impl Msweeper {
         }
     }
 
-    fn _start(&mut self, p: &Point<usize>) {
-        self.started = true;
-        self._place_mine(p);
-        self._randomize(p);
+    // 地雷を置いてゲームを始める。推測なしで解ける盤面が見つからなければfalse
+    fn _start(&mut self, p: &Point<usize>) -> bool {
+        let mut rng = ChaCha8Rng::seed_from_u64(self.seed);
+        let excluded = self._safe_zone(p);
+        let begin = Instant::now();
+        loop {
+            self._place_mine(&excluded, &mut rng);
+            // 推測なしで解けない盤面は作り直す
+            if !self.no_guess || self._is_solvable(p) {
+                self.started = true;
+                self.bbbv = self._count_bbbv(false);
+                return true;
+            }
+            if begin.elapsed() >= self.no_guess_time_limit {
+                break;
+            }
+        }
+        for space in self.board.iter_mut().flatten() {
+            space.stype = SpaceType::EMPTY;
+        }
+        // 同じ盤面を探し直さないように、次に開いたときは続きの乱数から探す
+        self.seed = rng.gen();
+        return false;
     }
 
     pub fn clean(&mut self) {
         self.started = false;
+        self.finished = false;
         self.opened_num = 0;
         self.flag_num = 0;
+        self.hint_num = 0;
+        self.undo_used = false;
+        self.bbbv = 0;
+        self.exploded_bbbv = None;
+        self.recording.clear();
+        self.history.clear();
+        self.redo_history.clear();
+        self.seed = match self.fixed_seed {
+            Some(seed) => seed,
+            None => rand::thread_rng().gen(),
+        };
+        self.changes.clear();
         // for only set unopened.
-        self.term.clear();
         self._clean_board();
-        self._print_controls();
-        self.print_all_spaces();
-        self.flush();
     }
 
-    fn _calc_some(
-        &mut self,
-        p: &Point<isize>,
-        func: fn(&Msweeper, &Point<isize>) -> usize,
-    ) -> usize {
+    fn _calc_some(&self, p: &Point<isize>, func: fn(&Msweeper, &Point<isize>) -> usize) -> usize {
         let mut num = 0;
 
         for pos8 in Point::pos8_iter() {
@@ -235,117 +562,172 @@ impl Msweeper {
         }
     }
 
-    fn _calc_mnum(&mut self, p: &Point<isize>) -> usize {
-        return self._calc_some(p, Self::_is_mine);
-    }
-
-    fn _calc_flag(&mut self, p: &Point<isize>) -> usize {
-        return self._calc_some(p, Self::_is_flag);
+    fn _is_unopened(&self, p: &Point<isize>) -> usize {
+        if !self._is_inbound(p) {
+            return 0;
+        }
+        match self.board[p.y as usize][p.x as usize].state {
+            SpaceState::UNOPENED | SpaceState::QUESTION => return 1,
+            _ => return 0,
+        }
     }
 
-    fn _stdout_space(&mut self, print_str: &str, p: &Point<usize>) {
-        self.term.print(
-            print_str,
-            self.board_offset.x + p.x * 2,
-            self.board_offset.y + p.y,
-        );
+    fn _calc_mnum(&self, p: &Point<isize>) -> usize {
+        return self._calc_some(p, Self::_is_mine);
     }
 
-    fn _print_empty(&mut self, p: &Point<usize>) {
-        let mnum = self._calc_mnum(&p.utoi());
-        self.term.color_bg(color::Black);
-        let str = match mnum {
-            0 => {
-                self.term.color_fg(color::Reset);
-                format!("  ")
-            }
-            n if (n > 0 && n <= 9) => {
-                match n {
-                    1 => self.term.color_fg(color::Blue),
-                    2 => self.term.color_fg(color::Green),
-                    3 => self.term.color_fg(color::Red),
-                    4 => self.term.color_fg(color::Cyan),
-                    5 => self.term.color_fg(color::Magenta),
-                    6 => self.term.color_fg(color::LightBlue),
-                    7 => self.term.color_fg(color::LightGreen),
-                    8 => self.term.color_fg(color::LightRed),
-                    9 => self.term.color_fg(color::Cyan),
-                    _ => {}
-                }
-                format!("{} ", mnum)
-            }
-            _ => format!("??"),
-        };
-        self._stdout_space(&str, p);
+    fn _calc_flag(&self, p: &Point<isize>) -> usize {
+        return self._calc_some(p, Self::_is_flag);
     }
 
-    fn _print_unopened(&mut self, p: &Point<usize>) {
-        self.term.color_fg(color::White);
-        self.term.color_bg(color::LightBlack);
-        self._stdout_space("_|", p)
+    fn _calc_unopened(&self, p: &Point<isize>) -> usize {
+        return self._calc_some(p, Self::_is_unopened);
     }
 
-    fn _print_flag(&mut self, p: &Point<usize>) {
-        self.term.color_fg(color::Red);
-        self.term.color_bg(color::LightBlack);
-        self._stdout_space(&format!("P{}|", color::Fg(color::White)), p)
+    // マスの状態を変え、取り消せるように記録する
+    fn _set_state(&mut self, p: &Point<usize>, state: SpaceState) {
+        let before = std::mem::replace(&mut self.board[p.y][p.x].state, state.clone());
+        self.recording.push((p.clone(), before, state));
+        self.changes.push(MSChange::Space(p.clone()));
     }
 
-    fn _print_mine(&mut self, p: &Point<usize>) {
-        self.term.color_fg(color::White);
-        self.term.color_bg(color::Black);
-        self._stdout_space("* ", p)
+    fn _counters(&self) -> Counters {
+        return Counters {
+            opened_num: self.opened_num,
+            flag_num: self.flag_num,
+            finished: self.finished,
+        };
     }
 
-    fn _print_pressed_mine(&mut self, p: &Point<usize>) {
-        self.term.color_fg(color::White);
-        self.term.color_bg(color::Red);
-        self._stdout_space("* ", p)
+    fn _restore(&mut self, counters: &Counters) {
+        self.opened_num = counters.opened_num;
+        self.flag_num = counters.flag_num;
+        self.finished = counters.finished;
     }
 
-    fn _print_space(&mut self, p: &Point<usize>) {
-        match self.board[p.y][p.x].state {
-            SpaceState::UNOPENED => self._print_unopened(p),
-            SpaceState::OPENED => match self.board[p.y][p.x].stype {
-                SpaceType::MINE => self._print_mine(p),
-                SpaceType::EMPTY => self._print_empty(p),
-            },
-            SpaceState::FLAG => self._print_flag(p),
-        };
-    }
-
-    pub fn print_all_spaces(&mut self) {
-        for _y in 0..(self.board.len()) {
-            for _x in 0..(self.board[_y].len()) {
-                self._print_space(&Point::<usize> { x: _x, y: _y });
-            }
+    // 記録した変化を1回の操作として履歴に積み、変化を返す
+    fn _take_changes(&mut self, before: Counters) -> Vec<MSChange> {
+        if !self.recording.is_empty() {
+            let events = self
+                .changes
+                .iter()
+                .filter(|c| !matches!(c, MSChange::Space(_)))
+                .cloned()
+                .collect();
+            self.history.push(Step {
+                spaces: std::mem::take(&mut self.recording),
+                before,
+                after: self._counters(),
+                events,
+            });
+            self.redo_history.clear();
         }
-        self.term.color_bg(color::Reset);
+        return std::mem::take(&mut self.changes);
     }
 
     fn _flag(&mut self, p: &Point<usize>) {
         match self.board[p.y][p.x].state {
             SpaceState::UNOPENED => {
-                self.board[p.y][p.x].state = SpaceState::FLAG;
+                self._set_state(p, SpaceState::FLAG);
                 self.flag_num += 1;
             }
             SpaceState::FLAG => {
-                self.board[p.y][p.x].state = SpaceState::UNOPENED;
+                let next = if self.question_marks {
+                    SpaceState::QUESTION
+                } else {
+                    SpaceState::UNOPENED
+                };
+                self._set_state(p, next);
                 self.flag_num -= 1;
             }
+            SpaceState::QUESTION => self._set_state(p, SpaceState::UNOPENED),
             _ => {}
         }
-        self._print_space(p);
     }
 
-    pub fn flag(&mut self, cursor_x: usize, cursor_y: usize) {
-        let result = self._get_board_press_pos(cursor_x, cursor_y);
-        if result.is_none() {
+    pub fn flag(&mut self, p: &Point<usize>) -> Vec<MSChange> {
+        if self.finished || !self._is_inbound(&p.utoi()) {
+            return Vec::new();
+        }
+
+        let before = self._counters();
+        self._flag(p);
+
+        return self._take_changes(before);
+    }
+
+    // 周りの未開封のマスが残りの地雷の数と同じなら、すべてに旗を立てる
+    fn _flag_8(&mut self, p: &Point<isize>) {
+        let unopened = self._calc_unopened(p);
+        if (unopened == 0) || (self._calc_flag(p) + unopened != self._calc_mnum(p)) {
+            // 何もしない
             return;
         }
-        let p = result.unwrap();
 
-        self._flag(&p);
+        for pos8 in Point::pos8_iter() {
+            let n = p.get_pos_8(pos8);
+            if self._is_unopened(&n) == 1 {
+                self._set_state(&n.itou(), SpaceState::FLAG);
+                self.flag_num += 1;
+            }
+        }
+    }
+
+    // 開いた数字のマスの周りに旗を立てる
+    pub fn flag_chord(&mut self, p_u: &Point<usize>) -> Vec<MSChange> {
+        let p_i = p_u.utoi();
+        if self.finished
+            || !self._is_inbound(&p_i)
+            || (self.board[p_u.y][p_u.x].state != SpaceState::OPENED)
+        {
+            return Vec::new();
+        }
+
+        let before = self._counters();
+        self._flag_8(&p_i);
+
+        return self._take_changes(before);
+    }
+
+    // 直前の操作を取り消す。練習モードでなければ終わったゲームは戻せない
+    pub fn undo(&mut self) -> Vec<MSChange> {
+        if self.finished && !self.practice {
+            return Vec::new();
+        }
+        let Some(step) = self.history.pop() else {
+            return Vec::new();
+        };
+        let mut changes = Vec::new();
+        for (p, before, _) in step.spaces.iter().rev() {
+            self.board[p.y][p.x].state = before.clone();
+            changes.push(MSChange::Space(p.clone()));
+        }
+        self._restore(&step.before);
+        if step.after.finished && !step.before.finished {
+            changes.push(MSChange::Resumed);
+        }
+        self.undo_used = true;
+        self.redo_history.push(step);
+        return changes;
+    }
+
+    // 取り消した操作をやり直す
+    pub fn redo(&mut self) -> Vec<MSChange> {
+        if self.finished {
+            return Vec::new();
+        }
+        let Some(step) = self.redo_history.pop() else {
+            return Vec::new();
+        };
+        let mut changes = Vec::new();
+        for (p, _, after) in &step.spaces {
+            self.board[p.y][p.x].state = after.clone();
+            changes.push(MSChange::Space(p.clone()));
+        }
+        self._restore(&step.after);
+        changes.extend(step.events.iter().cloned());
+        self.history.push(step);
+        return changes;
     }
 
     fn _is_inbound(&self, p: &Point<isize>) -> bool {
@@ -364,12 +746,15 @@ impl Msweeper {
         if !self._is_inbound(&p.utoi()) {
             return;
         }
-        self.board[p.y][p.x].state = SpaceState::OPENED;
+        if self.board[p.y][p.x].state == SpaceState::FLAG {
+            self.flag_num -= 1;
+        }
+        self._set_state(p, SpaceState::OPENED);
         self.opened_num += 1;
     }
 
     fn _open(&mut self, p_i: &Point<isize>) {
-        if !self._is_inbound(&p_i) {
+        if !self._is_inbound(p_i) {
             return;
         }
         let p_u = p_i.itou();
@@ -379,8 +764,7 @@ impl Msweeper {
             return;
         }
         self._set_open(&p_u);
-        self._print_space(&p_u);
-        if self._calc_mnum(&p_i) > 0 {
+        if self._calc_mnum(p_i) > 0 {
             return;
         }
 
@@ -389,42 +773,23 @@ impl Msweeper {
         }
     }
 
-    fn _get_press_pos(&mut self, cursor_x: usize, cursor_y: usize) -> Option<Point<usize>> {
-        let b_x = self.board_offset.x + 1;
-        let b_y = self.board_offset.y + 1;
-        if (cursor_x < (b_x)) || (cursor_y < b_y) {
-            return None;
-        }
-        return Some(Point::<usize> {
-            x: (cursor_x - b_x) / 2,
-            y: cursor_y - b_y,
-        });
-    }
-
-    fn _get_board_press_pos(&mut self, cursor_x: usize, cursor_y: usize) -> Option<Point<usize>> {
-        let p = self._get_press_pos(cursor_x, cursor_y)?;
-        if !self._is_inbound(&p.utoi()) {
-            return None;
-        }
-        return Some(p);
-    }
-
-    fn _open_all(&mut self, p: &Point<usize>) {
+    fn _open_all(&mut self) {
         for y in 0..(self.board.len()) {
             for x in 0..(self.board[y].len()) {
                 let pidx = Point::<usize> { x, y };
-                self._set_open(&pidx);
-                self._print_space(&pidx);
-                if pidx.is_equal(p) {
-                    self._print_pressed_mine(p);
+                if self.board[y][x].state != SpaceState::OPENED {
+                    self._set_open(&pidx);
                 }
             }
         }
     }
 
     fn _open_mine(&mut self, p: &Point<usize>) {
-        self._open_all(p);
-        self._print_gameover();
+        self.exploded_bbbv = Some(self._count_bbbv(true));
+        self._open_all();
+        self.finished = true;
+        self.changes.push(MSChange::Exploded(p.clone()));
+        self.changes.push(MSChange::GameOver);
     }
 
     fn _open_1(&mut self, p_i: &Point<isize>) -> bool {
@@ -456,38 +821,67 @@ impl Msweeper {
         }
 
         for pos8 in Point::pos8_iter() {
+            // 一度地雷を踏んだら以降は開けない
+            if rv {
+                break;
+            }
             rv |= self._open_1(&p.get_pos_8(pos8));
         }
 
         return rv;
     }
 
-    pub fn open(&mut self, cursor_x: usize, cursor_y: usize) -> bool {
-        let result = self._get_board_press_pos(cursor_x, cursor_y);
-        if result.is_none() {
-            return false;
-        }
-        let p_u = result.unwrap();
+    // 指定したマスを開く。変化したマスとゲーム状態のイベントを返す
+    pub fn open(&mut self, p_u: &Point<usize>) -> Vec<MSChange> {
         let p_i = p_u.utoi();
+        if self.finished || !self._is_inbound(&p_i) {
+            return Vec::new();
+        }
 
-        if !self.started {
-            self._start(&p_u);
+        if !self.started && !self._start(p_u) {
+            return vec![MSChange::NoGuessFailed];
         }
 
+        let before = self._counters();
         let rv = match self.board[p_u.y][p_u.x].state {
             SpaceState::FLAG => false,
-            SpaceState::UNOPENED => self._open_1(&p_i),
+            SpaceState::UNOPENED | SpaceState::QUESTION => self._open_1(&p_i),
             SpaceState::OPENED => self._open_8(&p_i),
         };
+        self._check_clear(rv);
 
-        if (rv == false) && self.is_clear() {
-            self._print_gameclear();
+        return self._take_changes(before);
+    }
+
+    // 開いたマスの周りだけを開く。未開封のマスには何もしない
+    pub fn chord(&mut self, p_u: &Point<usize>) -> Vec<MSChange> {
+        let p_i = p_u.utoi();
+        if self.finished
+            || !self._is_inbound(&p_i)
+            || (self.board[p_u.y][p_u.x].state != SpaceState::OPENED)
+        {
+            return Vec::new();
         }
 
-        return rv;
+        let before = self._counters();
+        let rv = self._open_8(&p_i);
+        self._check_clear(rv);
+
+        return self._take_changes(before);
+    }
+
+    fn _check_clear(&mut self, exploded: bool) {
+        if !exploded && self.is_clear() {
+            self.finished = true;
+            self.changes.push(MSChange::GameClear);
+        }
     }
 
+    // 地雷を踏んだときも盤面を全部開くので、開いた数だけでは決めない
     pub fn is_clear(&self) -> bool {
+        if self._is_exploded() {
+            return false;
+        }
         let remain_space_num = self.width() * self.height() - self.mine_num;
         if remain_space_num <= self.opened_num {
             return true;
@@ -495,14 +889,81 @@ impl Msweeper {
         return false;
     }
 
-    pub fn construct(width: usize, height: usize, mine_num: usize) -> Result<Msweeper, String> {
+    pub fn to_state(&self) -> MsweeperState {
+        return MsweeperState {
+            mine_num: self.mine_num,
+            fixed_seed: self.fixed_seed,
+            seed: self.seed,
+            no_guess: self.no_guess,
+            first_click: self.first_click.clone(),
+            started: self.started,
+            opened_num: self.opened_num,
+            flag_num: self.flag_num,
+            hint_num: self.hint_num,
+            practice: self.practice,
+            undo_used: self.undo_used,
+            board: self.board.clone(),
+        };
+    }
+
+    // 保存した状態から再開する。盤面と数が食い違っていればエラー
+    pub fn from_state(state: MsweeperState) -> Result<Msweeper, String> {
+        let height = state.board.len();
+        let width = state.board.first().map_or(0, |row| row.len());
+        let mut msweeper = Self::construct(width, height, state.mine_num, state.fixed_seed)?;
+        if state.board.iter().any(|row| row.len() != width) {
+            return Err("The saved board is not rectangular.".to_string());
+        }
+        let spaces = state.board.iter().flatten();
+        let mine_num = spaces
+            .clone()
+            .filter(|sp| sp.stype == SpaceType::MINE)
+            .count();
+        let opened_num = spaces
+            .clone()
+            .filter(|sp| sp.state == SpaceState::OPENED)
+            .count();
+        let flag_num = spaces.filter(|sp| sp.state == SpaceState::FLAG).count();
+        if (state.started && mine_num != state.mine_num)
+            || (opened_num != state.opened_num)
+            || (flag_num != state.flag_num)
+        {
+            return Err(format!(
+                "The saved counts do not match the board. mine_num = {}/{}, opened_num = {}/{}, flag_num = {}/{}",
+                mine_num, state.mine_num, opened_num, state.opened_num, flag_num, state.flag_num
+            ));
+        }
+
+        msweeper.seed = state.seed;
+        msweeper.no_guess = state.no_guess;
+        msweeper.first_click = state.first_click;
+        msweeper.started = state.started;
+        msweeper.opened_num = state.opened_num;
+        msweeper.flag_num = state.flag_num;
+        msweeper.hint_num = state.hint_num;
+        msweeper.practice = state.practice;
+        msweeper.undo_used = state.undo_used;
+        msweeper.board = state.board;
+        msweeper.finished = msweeper.started && (msweeper._is_exploded() || msweeper.is_clear());
+        if msweeper.started {
+            msweeper.bbbv = msweeper._count_bbbv(false);
+        }
+        return Ok(msweeper);
+    }
+
+    pub fn construct(
+        width: usize,
+        height: usize,
+        mine_num: usize,
+        seed: Option<u64>,
+    ) -> Result<Msweeper, String> {
         if mine_num >= width * height {
             return Err(format!(
                 "The number of mines exceeds board size. width = {}, height = {}, mine_num = {}",
                 width, height, mine_num
             ));
         }
-        if (width <= 0) || (height <= 0) {
+        if (width == 0) || (height == 0) {
             return Err(format!(
                 "Invalid width or height. width = {}, height = {}, mine_num = {}",
                 width, height, mine_num
@@ -512,18 +973,29 @@ impl Msweeper {
             state: SpaceState::UNOPENED,
             stype: SpaceType::EMPTY,
         };
-        let mut _board = Box::new(vec![vec![_space.clone(); width]; height]);
+        let _board = vec![vec![_space.clone(); width]; height];
         let mut msweeper = Msweeper {
             mine_num,
-            term: Terminal::construct(),
-            board_offset: Self::DEFAULT_BOARD_OFFSET,
-            remain_offset: Self::DEFAULT_REMAIN_OFFSET,
-            controls_offset: Self::DEFAULT_CONTROLS_OFFSET,
-            cmn_msg_offset: Self::DEFAULT_CMN_MSG_OFFSET,
+            fixed_seed: seed,
+            seed: 0,
+            no_guess: false,
+            no_guess_time_limit: Self::NO_GUESS_TIME_LIMIT,
+            first_click: FirstClick::SafeCell,
             started: false,
+            finished: false,
             opened_num: 0,
             flag_num: 0,
+            hint_num: 0,
+            practice: false,
+            undo_used: false,
+            question_marks: false,
+            bbbv: 0,
+            exploded_bbbv: None,
             board: _board,
+            changes: Vec::new(),
+            recording: Vec::new(),
+            history: Vec::new(),
+            redo_history: Vec::new(),
         };
 
         msweeper.clean();
@@ -531,3 +1003,348 @@ impl Msweeper {
         return Ok(msweeper);
     }
 }
+
+#[cfg(test)]
+impl Msweeper {
+    // ASCIIの盤面からテスト用に作る。地雷数は盤面から数える
+    pub(crate) fn set_no_guess_time_limit(&mut self, limit: Duration) {
+        self.no_guess_time_limit = limit;
+    }
+
+    pub(crate) fn from_board(text: &str) -> Msweeper {
+        let spaces = crate::board::parse(text).unwrap();
+        let mut ms = Msweeper::construct(spaces[0].len(), spaces.len(), 0, Some(0)).unwrap();
+        ms.set_board(&spaces).unwrap();
+        return ms;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TRIALS: u64 = 20000;
+
+    // 各マスが地雷になった回数を数える
+    fn count_mines(
+        width: usize,
+        height: usize,
+        mine_num: usize,
+        first_click: FirstClick,
+        press: &Point<usize>,
+    ) -> Vec<Vec<u64>> {
+        let mut ms = Msweeper::construct(width, height, mine_num, None).unwrap();
+        ms.set_first_click(first_click);
+        let excluded = ms._safe_zone(press);
+        let mut counts = vec![vec![0; width]; height];
+        for seed in 0..TRIALS {
+            let mut rng = ChaCha8Rng::seed_from_u64(seed);
+            ms._place_mine(&excluded, &mut rng);
+            let mut placed = 0;
+            for y in 0..height {
+                for x in 0..width {
+                    if ms.board[y][x].stype == SpaceType::MINE {
+                        counts[y][x] += 1;
+                        placed += 1;
+                    }
+                }
+            }
+            assert_eq!(placed, mine_num);
+        }
+        return counts;
+    }
+
+    // 除外したマスは地雷にならず、それ以外のマスは同じ確率で地雷になる
+    fn assert_uniform(
+        width: usize,
+        height: usize,
+        mine_num: usize,
+        first_click: FirstClick,
+        press: &Point<usize>,
+    ) {
+        let mut ms = Msweeper::construct(width, height, mine_num, None).unwrap();
+        ms.set_first_click(first_click.clone());
+        let excluded = ms._safe_zone(press);
+        let counts = count_mines(width, height, mine_num, first_click, press);
+
+        let allowed = (width * height - excluded.len()) as f64;
+        let p = mine_num as f64 / allowed;
+        // 5シグマを許容範囲とする
+        let tolerance = 5.0 * (p * (1.0 - p) / TRIALS as f64).sqrt();
+        for y in 0..height {
+            for x in 0..width {
+                let freq = counts[y][x] as f64 / TRIALS as f64;
+                if Msweeper::_is_excluded(&excluded, x, y) {
+                    assert_eq!(counts[y][x], 0, "excluded ({}, {}) got a mine", x, y);
+                } else {
+                    assert!(
+                        (freq - p).abs() <= tolerance,
+                        "({}, {}) freq = {}, expected = {} +- {}",
+                        x,
+                        y,
+                        freq,
+                        p,
+                        tolerance
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn uniform_square_safe_cell() {
+        assert_uniform(9, 9, 10, FirstClick::SafeCell, &Point { x: 4, y: 4 });
+    }
+
+    #[test]
+    fn uniform_square_classic() {
+        assert_uniform(8, 8, 20, FirstClick::Classic, &Point { x: 0, y: 0 });
+    }
+
+    #[test]
+    fn uniform_wide_safe_opening() {
+        assert_uniform(16, 5, 20, FirstClick::SafeOpening, &Point { x: 15, y: 2 });
+    }
+
+    #[test]
+    fn uniform_tall_safe_cell() {
+        assert_uniform(4, 13, 12, FirstClick::SafeCell, &Point { x: 3, y: 12 });
+    }
+
+    #[test]
+    fn uniform_dense_board() {
+        // 除外したマス以外がすべて地雷になる
+        assert_uniform(5, 3, 14, FirstClick::SafeCell, &Point { x: 2, y: 1 });
+    }
+
+    fn states(ms: &Msweeper) -> Vec<Vec<SpaceState>> {
+        return ms
+            .board
+            .iter()
+            .map(|row| row.iter().map(|sp| sp.state.clone()).collect())
+            .collect();
+    }
+
+    fn find_space(ms: &Msweeper, stype: SpaceType, state: SpaceState) -> Point<usize> {
+        for y in 0..ms.height() {
+            for x in 0..ms.width() {
+                if ms.board[y][x].stype == stype && ms.board[y][x].state == state {
+                    return Point { x, y };
+                }
+            }
+        }
+        panic!("no such space");
+    }
+
+    #[test]
+    fn undo_restores_counters_and_spaces() {
+        let mut ms = Msweeper::construct(9, 9, 10, Some(1)).unwrap();
+        ms.open(&Point { x: 4, y: 4 });
+        let opened = states(&ms);
+        let (opened_num, flag_num) = (ms.opened_num, ms.flag_num);
+
+        let mine = find_space(&ms, SpaceType::MINE, SpaceState::UNOPENED);
+        ms.flag(&mine);
+        let empty = find_space(&ms, SpaceType::EMPTY, SpaceState::UNOPENED);
+        ms.open(&empty);
+        let after = states(&ms);
+        let (after_opened, after_flag) = (ms.opened_num, ms.flag_num);
+
+        ms.undo();
+        ms.undo();
+        assert!(states(&ms) == opened);
+        assert_eq!((ms.opened_num, ms.flag_num), (opened_num, flag_num));
+        assert!(ms.is_undo_used());
+
+        ms.redo();
+        ms.redo();
+        assert!(states(&ms) == after);
+        assert_eq!((ms.opened_num, ms.flag_num), (after_opened, after_flag));
+    }
+
+    #[test]
+    fn fatal_click_can_be_undone_only_in_practice() {
+        for practice in [false, true] {
+            let mut ms = Msweeper::construct(9, 9, 10, Some(2)).unwrap();
+            ms.set_practice(practice);
+            ms.open(&Point { x: 0, y: 0 });
+            let before = states(&ms);
+            let mine = find_space(&ms, SpaceType::MINE, SpaceState::UNOPENED);
+            ms.open(&mine);
+            assert!(ms.is_finished());
+
+            let changes = ms.undo();
+            assert_eq!(!ms.is_finished(), practice);
+            assert_eq!(states(&ms) == before, practice);
+            assert_eq!(
+                changes.iter().any(|c| matches!(c, MSChange::Resumed)),
+                practice
+            );
+        }
+    }
+
+    #[test]
+    fn same_seed_gives_the_same_layout_everywhere() {
+        // 乱数の作り方が変わると同じシードでも別の盤面になるので、結果を固定して確かめる
+        let mut ms = Msweeper::construct(9, 9, 10, Some(12345)).unwrap();
+        ms.set_first_click(FirstClick::SafeCell);
+        ms.open(&Point { x: 4, y: 4 });
+        let expected = [
+            "..**....*",
+            ".......*.",
+            ".......*.",
+            ".*.......",
+            ".........",
+            ".........",
+            "*....**..",
+            ".*.......",
+            ".........",
+        ];
+        let layout: Vec<String> = ms
+            .mine_layout()
+            .iter()
+            .map(|row| row.iter().map(|&m| if m { '*' } else { '.' }).collect())
+            .collect();
+        assert_eq!(layout, expected);
+    }
+
+    #[test]
+    fn no_guess_boards_are_cleared_by_the_solver() {
+        for seed in 0..5 {
+            let mut ms = Msweeper::construct(16, 16, 40, Some(seed)).unwrap();
+            ms.set_no_guess(true);
+            ms.open(&Point { x: 8, y: 8 });
+            // 確実に分かるマスだけを開け、旗を立て続ける
+            while !ms.is_finished() {
+                let deductions = Solver::from_msweeper(&ms).deduce();
+                assert!(!deductions.is_empty(), "seed {} needs a guess", seed);
+                for d in &deductions.mines {
+                    ms.flag(&d.pos);
+                }
+                for d in &deductions.safe {
+                    ms.open(&d.pos);
+                }
+            }
+            assert!(ms.is_clear() && !ms._is_exploded(), "seed {}", seed);
+        }
+    }
+
+    #[test]
+    fn no_guess_failure_is_reported() {
+        // 残りの1マスがどこか分からないので推測なしでは解けない
+        let mut ms = Msweeper::construct(4, 4, 14, Some(0)).unwrap();
+        ms.set_no_guess(true);
+        ms.set_no_guess_time_limit(Duration::from_millis(20));
+        let changes = ms.open(&Point { x: 0, y: 0 });
+        assert!(matches!(changes[..], [MSChange::NoGuessFailed]));
+        assert!(!ms.is_started());
+        assert!(ms.mine_layout().iter().flatten().all(|&m| !m));
+
+        // もう一度開くと前と違う盤面から探す
+        let seed = ms.seed();
+        assert_ne!(seed, 0);
+        ms.open(&Point { x: 0, y: 0 });
+        assert_ne!(ms.seed(), seed);
+    }
+
+    #[test]
+    fn lost_game_is_not_clear() {
+        let mut ms = Msweeper::from_board("..*..\n");
+        ms.open(&Point { x: 2, y: 0 });
+        assert!(ms.is_finished() && !ms.is_clear());
+        let resumed = Msweeper::from_state(ms.to_state()).unwrap();
+        assert!(resumed.is_finished() && !resumed.is_clear());
+    }
+
+    #[test]
+    fn chord_opens_around_a_satisfied_number_only() {
+        let mut ms = Msweeper::from_board("*1.\n11.\n...\n");
+        assert!(ms.chord(&Point { x: 1, y: 1 }).is_empty());
+        ms.flag(&Point { x: 0, y: 0 });
+        assert!(ms.chord(&Point { x: 2, y: 2 }).is_empty());
+
+        let changes = ms.chord(&Point { x: 1, y: 1 });
+        assert!(changes.iter().any(|c| matches!(c, MSChange::GameClear)));
+        assert!(ms.is_clear());
+    }
+
+    #[test]
+    fn question_mark_follows_the_flag_and_is_not_a_flag() {
+        let mut ms = Msweeper::from_board("*1.\n11.\n...\n");
+        let mine = Point { x: 0, y: 0 };
+        ms.flag(&mine);
+        assert_eq!(ms.remain(), 0);
+        ms.flag(&mine);
+        assert!(ms.space(&mine).state == SpaceState::UNOPENED);
+
+        ms.set_question_marks(true);
+        ms.flag(&mine);
+        ms.flag(&mine);
+        assert!(ms.space(&mine).state == SpaceState::QUESTION);
+        assert_eq!(ms.remain(), 1);
+        assert!(ms.chord(&Point { x: 1, y: 1 }).is_empty());
+        ms.flag(&mine);
+        assert!(ms.space(&mine).state == SpaceState::UNOPENED);
+    }
+
+    #[test]
+    fn flag_chord_flags_the_remaining_neighbors() {
+        let mut ms = Msweeper::from_board("*2*\n121\n...\n");
+        // 2の周りの未開封は5マスなので決まらない
+        assert!(ms.flag_chord(&Point { x: 1, y: 1 }).is_empty());
+
+        let changes = ms.flag_chord(&Point { x: 1, y: 0 });
+        assert_eq!(changes.len(), 2);
+        assert_eq!(ms.remain(), 0);
+        assert!(ms.space(&Point { x: 2, y: 0 }).state == SpaceState::FLAG);
+        assert!(ms.space(&Point { x: 1, y: 2 }).state == SpaceState::UNOPENED);
+
+        ms.undo();
+        assert_eq!(ms.remain(), 2);
+    }
+
+    #[test]
+    fn saved_state_resumes_the_same_game() {
+        let mut ms = Msweeper::construct(9, 9, 10, Some(3)).unwrap();
+        ms.open(&Point { x: 4, y: 4 });
+        let mine = find_space(&ms, SpaceType::MINE, SpaceState::UNOPENED);
+        ms.flag(&mine);
+
+        let json = serde_json::to_string(&ms.to_state()).unwrap();
+        let resumed = Msweeper::from_state(serde_json::from_str(&json).unwrap()).unwrap();
+        assert!(states(&resumed) == states(&ms));
+        assert!(resumed.mine_layout() == ms.mine_layout());
+        assert_eq!(resumed.remain(), ms.remain());
+        assert!(resumed.is_started() && !resumed.is_finished());
+
+        let mut broken = ms.to_state();
+        broken.flag_num += 1;
+        assert!(Msweeper::from_state(broken).is_err());
+    }
+
+    #[test]
+    fn bbbv_counts_openings_and_isolated_numbers() {
+        // 0の領域が1つと、0に接していない上の段の2
+        let mut ms = Msweeper::construct(3, 3, 2, Some(0)).unwrap();
+        ms.set_mine_layout(&[
+            vec![true, false, true],
+            vec![false, false, false],
+            vec![false, false, false],
+        ])
+        .unwrap();
+        assert_eq!(ms.bbbv(), 2);
+        assert_eq!(ms.solved_bbbv(), 0);
+
+        ms.open(&Point { x: 1, y: 0 });
+        assert_eq!(ms.solved_bbbv(), 1);
+        // 負けたときは全部開くが、開けていた分だけを数える
+        let mut lost = Msweeper::from_state(ms.to_state()).unwrap();
+        lost.open(&Point { x: 0, y: 0 });
+        assert!(lost.is_finished());
+        assert_eq!(lost.solved_bbbv(), 1);
+
+        ms.open(&Point { x: 1, y: 2 });
+        assert_eq!(ms.solved_bbbv(), 2);
+        assert!(ms.is_clear());
+    }
+}
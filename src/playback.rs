@@ -0,0 +1,144 @@
+use msweeper_rs::replay::{Input, Replay};
+use std::time::Instant;
+
+// リプレイの再生位置と速度
+pub struct Playback {
+    replay: Replay,
+    next: usize,
+    // 再生中のゲーム内の経過時間(ミリ秒)
+    clock_ms: f64,
+    speed: f64,
+    paused: bool,
+    last: Instant,
+}
+
+impl Playback {
+    const MIN_SPEED: f64 = 0.25;
+    const MAX_SPEED: f64 = 16.0;
+
+    pub fn construct(replay: Replay) -> Playback {
+        return Playback {
+            replay,
+            next: 0,
+            clock_ms: 0.0,
+            speed: 1.0,
+            paused: false,
+            last: Instant::now(),
+        };
+    }
+
+    pub fn replay(&self) -> &Replay {
+        &self.replay
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.next >= self.replay.inputs.len()
+    }
+
+    // 時間を進め、その間に行われた入力を返す
+    pub fn tick(&mut self) -> Vec<Input> {
+        let now = Instant::now();
+        if !self.paused && !self.is_finished() {
+            self.clock_ms += now.duration_since(self.last).as_secs_f64() * 1000.0 * self.speed;
+        }
+        self.last = now;
+
+        let mut rv = Vec::new();
+        while let Some(input) = self.replay.inputs.get(self.next) {
+            if input.time_ms as f64 > self.clock_ms {
+                break;
+            }
+            rv.push(input.clone());
+            self.next += 1;
+        }
+        return rv;
+    }
+
+    // 一時停止して次の入力だけを進める
+    pub fn step(&mut self) -> Option<Input> {
+        self.paused = true;
+        let input = self.replay.inputs.get(self.next)?.clone();
+        self.clock_ms = input.time_ms as f64;
+        self.next += 1;
+        return Some(input);
+    }
+
+    pub fn toggle_pause(&mut self) {
+        self.paused = !self.paused;
+    }
+
+    pub fn faster(&mut self) {
+        self.speed = (self.speed * 2.0).min(Self::MAX_SPEED);
+    }
+
+    pub fn slower(&mut self) {
+        self.speed = (self.speed / 2.0).max(Self::MIN_SPEED);
+    }
+
+    pub fn status(&self) -> String {
+        let state = if self.is_finished() {
+            "finished"
+        } else if self.paused {
+            "paused"
+        } else {
+            "playing"
+        };
+        return format!(
+            "[Replay] {} x{} {}/{} {:.1}s  Space: pause, 'n': step, '+'/'-': speed, 'q': quit",
+            state,
+            self.speed,
+            self.next,
+            self.replay.inputs.len(),
+            self.clock_ms / 1000.0
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use msweeper_rs::msweeper::Msweeper;
+    use msweeper_rs::replay::Action;
+
+    fn replay(times: &[u64]) -> Replay {
+        let ms = Msweeper::construct(2, 2, 1, Some(0)).unwrap();
+        let inputs = times
+            .iter()
+            .map(|&time_ms| Input {
+                time_ms,
+                action: Action::Open,
+                x: 0,
+                y: 0,
+            })
+            .collect();
+        return Replay::construct(&ms, Vec::new(), inputs);
+    }
+
+    #[test]
+    fn step_pauses_and_plays_one_input() {
+        let mut playback = Playback::construct(replay(&[0, 5000, 10000]));
+        assert_eq!(playback.tick().len(), 1);
+        assert_eq!(playback.step().unwrap().time_ms, 5000);
+        assert!(playback.status().starts_with("[Replay] paused x1 2/3 5.0s"));
+        // 一時停止中は時間が進まない
+        assert!(playback.tick().is_empty());
+
+        playback.step();
+        assert!(playback.is_finished());
+        assert!(playback.step().is_none());
+        assert!(playback.status().starts_with("[Replay] finished"));
+    }
+
+    #[test]
+    fn speed_stays_within_the_limits() {
+        let mut playback = Playback::construct(replay(&[]));
+        for _ in 0..10 {
+            playback.faster();
+        }
+        assert!(playback.status().contains(" x16 "));
+        for _ in 0..10 {
+            playback.slower();
+        }
+        assert!(playback.status().contains(" x0.25 "));
+    }
+}
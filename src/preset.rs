@@ -0,0 +1,103 @@
+use serde::Deserialize;
+use std::collections::BTreeMap;
+
+// 名前で選べる盤面の大きさと地雷数
+#[derive(Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Preset {
+    pub width: usize,
+    pub height: usize,
+    #[serde(rename = "mines")]
+    pub mine_num: usize,
+}
+
+const BUILTIN: [(&str, Preset); 3] = [
+    (
+        "beginner",
+        Preset {
+            width: 9,
+            height: 9,
+            mine_num: 10,
+        },
+    ),
+    (
+        "intermediate",
+        Preset {
+            width: 16,
+            height: 16,
+            mine_num: 40,
+        },
+    ),
+    (
+        "expert",
+        Preset {
+            width: 30,
+            height: 16,
+            mine_num: 99,
+        },
+    ),
+];
+
+// 組み込みのプリセットを先に探し、なければ設定ファイルで定義されたものを探す
+pub fn find(name: &str, user: &BTreeMap<String, Preset>) -> Result<Preset, String> {
+    if let Some((_, preset)) = BUILTIN.iter().find(|(n, _)| *n == name) {
+        return Ok(preset.clone());
+    }
+    if let Some(preset) = user.get(name) {
+        return Ok(preset.clone());
+    }
+    // 組み込みと同じ名前の定義は使われないので並べない
+    let names: Vec<&str> = BUILTIN
+        .iter()
+        .map(|(n, _)| *n)
+        .chain(
+            user.keys()
+                .map(|n| n.as_str())
+                .filter(|n| !BUILTIN.iter().any(|(b, _)| b == n)),
+        )
+        .collect();
+    return Err(format!(
+        "Unknown preset '{}'. Available presets: {}",
+        name,
+        names.join(", ")
+    ));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn user() -> BTreeMap<String, Preset> {
+        let mut presets = BTreeMap::new();
+        for (name, width) in [("expert", 50), ("wide", 40)] {
+            presets.insert(
+                name.to_string(),
+                Preset {
+                    width,
+                    height: 8,
+                    mine_num: 20,
+                },
+            );
+        }
+        return presets;
+    }
+
+    #[test]
+    fn builtin_presets_come_first() {
+        let preset = find("expert", &user()).unwrap();
+        assert_eq!((preset.width, preset.height, preset.mine_num), (30, 16, 99));
+        let preset = find("wide", &user()).unwrap();
+        assert_eq!((preset.width, preset.height, preset.mine_num), (40, 8, 20));
+    }
+
+    #[test]
+    fn unknown_preset_lists_the_available_names() {
+        assert_eq!(
+            find("huge", &user()).err(),
+            Some(
+                "Unknown preset 'huge'. Available presets: beginner, intermediate, expert, wide"
+                    .to_string()
+            )
+        );
+    }
+}
@@ -0,0 +1,414 @@
+use crate::point::Point;
+use crate::solver::{Cell, Solver};
+
+// 未開封の各マスが地雷である確率
+pub struct Probabilities {
+    width: usize,
+    values: Vec<Option<f64>>,
+    // 配置を数えきれずに、残りの地雷を未開封のマスで等しく分けた値ならfalse
+    exact: bool,
+}
+
+impl Probabilities {
+    // 開いているマスと旗のマス、または矛盾した盤面ではNone
+    pub fn get(&self, p: &Point<usize>) -> Option<f64> {
+        self.values[p.y * self.width + p.x]
+    }
+
+    pub fn is_exact(&self) -> bool {
+        return self.exact;
+    }
+}
+
+// 配置を数える探索の手数の上限。境界が長いと探索は指数的に増えるので、
+// 超えたら正確な値をあきらめる
+const MAX_SEARCH_STEPS: usize = 200000;
+
+// 数字1つ分の制約。cellsは成分内での番号
+struct Constraint {
+    cells: Vec<usize>,
+    mines: usize,
+}
+
+// 数字でつながった未開封マスの集まり
+struct Component {
+    cells: Vec<usize>,
+    constraints: Vec<Constraint>,
+    // 地雷数ごとの配置の数
+    counts: Vec<f64>,
+    // 地雷数ごと、マスごとにそのマスが地雷である配置の数
+    cell_counts: Vec<Vec<f64>>,
+}
+
+impl Component {
+    // stepsは残りの手数。使い切ったらfalse
+    fn _enumerate(&mut self, steps: &mut usize) -> bool {
+        let n = self.cells.len();
+        self.counts = vec![0.0; n + 1];
+        self.cell_counts = vec![vec![0.0; n]; n + 1];
+
+        // マスごとに関係する制約
+        let mut related = vec![Vec::new(); n];
+        for (ci, c) in self.constraints.iter().enumerate() {
+            for &cell in &c.cells {
+                related[cell].push(ci);
+            }
+        }
+        let mut assigned_mines = vec![0; self.constraints.len()];
+        let mut unassigned: Vec<usize> = self.constraints.iter().map(|c| c.cells.len()).collect();
+        let mut assign = vec![false; n];
+        return self._search(
+            0,
+            &related,
+            &mut assigned_mines,
+            &mut unassigned,
+            &mut assign,
+            steps,
+        );
+    }
+
+    fn _search(
+        &mut self,
+        k: usize,
+        related: &[Vec<usize>],
+        assigned_mines: &mut [usize],
+        unassigned: &mut [usize],
+        assign: &mut [bool],
+        steps: &mut usize,
+    ) -> bool {
+        if *steps == 0 {
+            return false;
+        }
+        *steps -= 1;
+        if k == self.cells.len() {
+            let m = assign.iter().filter(|&&a| a).count();
+            self.counts[m] += 1.0;
+            for i in 0..assign.len() {
+                if assign[i] {
+                    self.cell_counts[m][i] += 1.0;
+                }
+            }
+            return true;
+        }
+        for is_mine in [false, true] {
+            assign[k] = is_mine;
+            let mut ok = true;
+            for &ci in &related[k] {
+                unassigned[ci] -= 1;
+                if is_mine {
+                    assigned_mines[ci] += 1;
+                }
+                let req = self.constraints[ci].mines;
+                if (assigned_mines[ci] > req) || (assigned_mines[ci] + unassigned[ci] < req) {
+                    ok = false;
+                }
+            }
+            if ok && !self._search(k + 1, related, assigned_mines, unassigned, assign, steps) {
+                return false;
+            }
+            for &ci in &related[k] {
+                unassigned[ci] += 1;
+                if is_mine {
+                    assigned_mines[ci] -= 1;
+                }
+            }
+        }
+        assign[k] = false;
+        return true;
+    }
+}
+
+fn _neighbors(solver: &Solver, idx: usize) -> Vec<usize> {
+    let p = Point::<isize> {
+        x: (idx % solver.width()) as isize,
+        y: (idx / solver.width()) as isize,
+    };
+    let mut rv = Vec::new();
+    for pos8 in Point::pos8_iter() {
+        let n = p.get_pos_8(pos8);
+        if (n.x < 0)
+            || (n.y < 0)
+            || (n.x as usize >= solver.width())
+            || (n.y as usize >= solver.height())
+        {
+            continue;
+        }
+        rv.push(n.y as usize * solver.width() + n.x as usize);
+    }
+    return rv;
+}
+
+fn _cell(solver: &Solver, idx: usize) -> &Cell {
+    return solver.cell(&Point::<usize> {
+        x: idx % solver.width(),
+        y: idx / solver.width(),
+    });
+}
+
+// 数字でつながった未開封マスを成分に分ける。
+// 隣接するマスから順に並べるので、探索中に早く制約が閉じる
+fn _components(solver: &Solver) -> Option<Vec<Component>> {
+    let size = solver.width() * solver.height();
+    // 数字ごとの未開封マスと残りの地雷数
+    let mut constraints: Vec<(Vec<usize>, usize)> = Vec::new();
+    let mut cell_constraints = vec![Vec::new(); size];
+    for idx in 0..size {
+        let num = match _cell(solver, idx) {
+            Cell::Number(n) => *n,
+            _ => continue,
+        };
+        let mut unknown = Vec::new();
+        let mut known_mines = 0;
+        for n in _neighbors(solver, idx) {
+            match _cell(solver, n) {
+                Cell::Unknown => unknown.push(n),
+                Cell::Mine => known_mines += 1,
+                Cell::Number(_) => {}
+            }
+        }
+        if (known_mines > num) || (known_mines + unknown.len() < num) {
+            // 矛盾している
+            return None;
+        }
+        if unknown.is_empty() {
+            continue;
+        }
+        for &u in &unknown {
+            cell_constraints[u].push(constraints.len());
+        }
+        constraints.push((unknown, num - known_mines));
+    }
+
+    let mut visited = vec![false; size];
+    let mut rv = Vec::new();
+    for start in 0..size {
+        if visited[start] || cell_constraints[start].is_empty() {
+            continue;
+        }
+        let mut cells = Vec::new();
+        let mut used = Vec::new();
+        let mut queue = std::collections::VecDeque::from([start]);
+        visited[start] = true;
+        while let Some(idx) = queue.pop_front() {
+            cells.push(idx);
+            for &ci in &cell_constraints[idx] {
+                if !used.contains(&ci) {
+                    used.push(ci);
+                }
+                for &n in &constraints[ci].0 {
+                    if !visited[n] {
+                        visited[n] = true;
+                        queue.push_back(n);
+                    }
+                }
+            }
+        }
+        let local = |idx: &usize| cells.iter().position(|c| c == idx).unwrap();
+        let component_constraints = used
+            .iter()
+            .map(|&ci| Constraint {
+                cells: constraints[ci].0.iter().map(local).collect(),
+                mines: constraints[ci].1,
+            })
+            .collect();
+        rv.push(Component {
+            cells,
+            constraints: component_constraints,
+            counts: Vec::new(),
+            cell_counts: Vec::new(),
+        });
+    }
+    return Some(rv);
+}
+
+fn _ln_choose(n: usize, k: usize) -> f64 {
+    if k > n {
+        return f64::NEG_INFINITY;
+    }
+    let mut rv = 0.0;
+    for i in 0..k.min(n - k) {
+        rv += ((n - i) as f64).ln() - ((i + 1) as f64).ln();
+    }
+    return rv;
+}
+
+// 成分ごとの地雷数の分布を畳み込む
+fn _convolve(components: &[Component], skip: Option<usize>) -> Vec<f64> {
+    let mut dist = vec![1.0];
+    for (i, c) in components.iter().enumerate() {
+        if Some(i) == skip {
+            continue;
+        }
+        let mut next = vec![0.0; dist.len() + c.counts.len() - 1];
+        for (a, &wa) in dist.iter().enumerate() {
+            if wa == 0.0 {
+                continue;
+            }
+            for (b, &wb) in c.counts.iter().enumerate() {
+                next[a + b] += wa * wb;
+            }
+        }
+        dist = next;
+    }
+    return dist;
+}
+
+// 残りの地雷を未開封のマスで等しく分け合った値。数えきれないときの近似に使う
+fn _uniform(solver: &Solver, remain: usize) -> Probabilities {
+    let size = solver.width() * solver.height();
+    let unknown: Vec<usize> = (0..size)
+        .filter(|&idx| *_cell(solver, idx) == Cell::Unknown)
+        .collect();
+    let mut values = vec![None; size];
+    for &idx in &unknown {
+        values[idx] = Some(remain as f64 / unknown.len() as f64);
+    }
+    return Probabilities {
+        width: solver.width(),
+        values,
+        exact: false,
+    };
+}
+
+// 盤面全体で矛盾しない地雷配置をすべて数え上げ、各マスが地雷である正確な確率を求める。
+// 数字に接していないマスは全体の地雷数から残った地雷を等しく分け合う。
+// 探索がMAX_SEARCH_STEPSを超えたら全体の密度で近似する
+pub fn calc(solver: &Solver) -> Probabilities {
+    let size = solver.width() * solver.height();
+    let mut rv = Probabilities {
+        width: solver.width(),
+        values: vec![None; size],
+        exact: true,
+    };
+
+    let known_mines = (0..size)
+        .filter(|&idx| *_cell(solver, idx) == Cell::Mine)
+        .count();
+    if known_mines > solver.mine_num() {
+        return rv;
+    }
+    let remain = solver.mine_num() - known_mines;
+
+    let Some(mut components) = _components(solver) else {
+        return rv;
+    };
+    let mut steps = MAX_SEARCH_STEPS;
+    for c in components.iter_mut() {
+        if !c._enumerate(&mut steps) {
+            return _uniform(solver, remain);
+        }
+    }
+
+    let mut is_frontier = vec![false; size];
+    for c in &components {
+        for &idx in &c.cells {
+            is_frontier[idx] = true;
+        }
+    }
+    let interior: Vec<usize> = (0..size)
+        .filter(|&idx| *_cell(solver, idx) == Cell::Unknown && !is_frontier[idx])
+        .collect();
+
+    // 境界の地雷数ごとの重み。残りの地雷を内側に置く組み合わせの数を掛ける。
+    // 桁あふれしないように対数で最大値をそろえる
+    let ln_weights: Vec<f64> = (0..=remain)
+        .map(|m| _ln_choose(interior.len(), remain - m))
+        .collect();
+    let ln_max = ln_weights.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+    if ln_max == f64::NEG_INFINITY {
+        return rv;
+    }
+    let weights: Vec<f64> = ln_weights.iter().map(|w| (w - ln_max).exp()).collect();
+    let weight = |m: usize| if m <= remain { weights[m] } else { 0.0 };
+
+    let all = _convolve(&components, None);
+    let total: f64 = all.iter().enumerate().map(|(m, w)| w * weight(m)).sum();
+    if total == 0.0 {
+        return rv;
+    }
+
+    for (i, c) in components.iter().enumerate() {
+        let others = _convolve(&components, Some(i));
+        for (k, &idx) in c.cells.iter().enumerate() {
+            let mut sum = 0.0;
+            for m in 0..c.counts.len() {
+                if c.cell_counts[m][k] == 0.0 {
+                    continue;
+                }
+                for (o, &wo) in others.iter().enumerate() {
+                    sum += c.cell_counts[m][k] * wo * weight(m + o);
+                }
+            }
+            rv.values[idx] = Some(sum / total);
+        }
+    }
+
+    if !interior.is_empty() {
+        // 内側の1マスが地雷である確率は、内側に残る地雷数の期待値をマス数で割ったもの
+        let expected: f64 = all
+            .iter()
+            .enumerate()
+            .filter(|(m, _)| *m <= remain)
+            .map(|(m, w)| w * weight(m) * (remain - m) as f64)
+            .sum();
+        let p = expected / total / interior.len() as f64;
+        for idx in interior {
+            rv.values[idx] = Some(p);
+        }
+    }
+
+    return rv;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_near(actual: Option<f64>, expected: f64) {
+        let actual = actual.unwrap();
+        assert!(
+            (actual - expected).abs() < 1e-9,
+            "actual = {}, expected = {}",
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn fifty_fifty() {
+        let probs = calc(&Solver::from_rows(&["..", "11", "00"], 1));
+        assert_near(probs.get(&Point { x: 0, y: 0 }), 0.5);
+        assert_near(probs.get(&Point { x: 1, y: 0 }), 0.5);
+        assert_eq!(probs.get(&Point { x: 0, y: 1 }), None);
+    }
+
+    #[test]
+    fn interior_shares_remaining_mines() {
+        // 境界の2マスに1個、内側の3マスに残りの1個
+        let probs = calc(&Solver::from_rows(&["...", "...", "11.", "00."], 2));
+        let frontier = probs.get(&Point { x: 0, y: 1 });
+        let interior = probs.get(&Point { x: 2, y: 0 });
+        assert_near(frontier, 0.5);
+        assert_near(interior, 1.0 / 3.0);
+    }
+
+    #[test]
+    fn global_count_weights_configurations() {
+        // 左の1は(0,0)か(1,0)、右の1は(1,0)か(2,0)。地雷が1個しかなければ(1,0)に決まる
+        let probs = calc(&Solver::from_rows(&["...", "111", "000"], 1));
+        assert_near(probs.get(&Point { x: 1, y: 0 }), 1.0);
+        assert_near(probs.get(&Point { x: 0, y: 0 }), 0.0);
+        assert!(probs.is_exact());
+    }
+
+    #[test]
+    fn long_frontier_falls_back_to_the_density() {
+        // 2の並びの上下にある68マスは、配置を数えると探索の上限を超える
+        let unknown = ".".repeat(34);
+        let twos = "2".repeat(34);
+        let probs = calc(&Solver::from_rows(&[&unknown, &twos, &unknown], 40));
+        assert!(!probs.is_exact());
+        assert_near(probs.get(&Point { x: 5, y: 0 }), 40.0 / 68.0);
+        assert_eq!(probs.get(&Point { x: 5, y: 1 }), None);
+    }
+}
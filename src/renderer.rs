@@ -0,0 +1,720 @@
+use crate::keys::Keys;
+use crate::msweeper::{MSChange, Msweeper, SpaceState, SpaceType};
+use crate::point::Point;
+use crate::terminal::Terminal;
+use crate::theme::Theme;
+use msweeper_rs::metrics::Metrics;
+use msweeper_rs::probability::Probabilities;
+use std::time::Duration;
+use termion::{color, style};
+
+// Msweeperの状態をtermionで描画する
+pub struct Renderer {
+    term: Terminal,
+    board_offset: Point<usize>,
+    remain_offset: Point<usize>,
+    controls_offset: Point<usize>,
+    cmn_msg_offset: Point<usize>,
+    summary_offset: Point<usize>,
+    // 設定されている間は未開封のマスに地雷の確率を重ねて表示する
+    probabilities: Option<Probabilities>,
+    // 踏んでしまった地雷
+    exploded: Option<Point<usize>>,
+    // ヒントで示しているマス
+    hint: Option<Point<usize>>,
+    // キーボードで操作するカーソル。色を反転して表示する
+    cursor: Option<Point<usize>>,
+    // 両ボタンを押している間、周りの未開封のマスをへこませて表示する
+    pressed: Option<Point<usize>>,
+    // 表示している範囲の左上のマスと、端末に収まるマスの数
+    scroll: Point<usize>,
+    view: Point<usize>,
+    // 最初に開いてからの経過時間
+    elapsed: Duration,
+    // 終わったゲームの3BVなどの表示
+    summary: Option<String>,
+    theme: Theme,
+    // 操作説明とやり直すキーの表示
+    controls: String,
+    reset_key: String,
+}
+
+impl Renderer {
+    const DEFAULT_BOARD_OFFSET: Point<usize> = Point { x: 2, y: 6 };
+    const DEFAULT_CONTROLS_OFFSET: Point<usize> = Point {
+        x: Self::DEFAULT_BOARD_OFFSET.x,
+        y: 1,
+    };
+    const DEFAULT_CMN_MSG_OFFSET: Point<usize> = Point {
+        x: Self::DEFAULT_BOARD_OFFSET.x,
+        y: 2,
+    };
+    const DEFAULT_SUMMARY_OFFSET: Point<usize> = Point {
+        x: Self::DEFAULT_BOARD_OFFSET.x,
+        y: 3,
+    };
+    const DEFAULT_REMAIN_OFFSET: Point<usize> = Point {
+        x: Self::DEFAULT_BOARD_OFFSET.x,
+        y: 4,
+    };
+
+    pub fn construct(theme: &Theme, keys: &Keys) -> Renderer {
+        return Renderer {
+            term: Terminal::construct(),
+            board_offset: Self::DEFAULT_BOARD_OFFSET,
+            remain_offset: Self::DEFAULT_REMAIN_OFFSET,
+            controls_offset: Self::DEFAULT_CONTROLS_OFFSET,
+            cmn_msg_offset: Self::DEFAULT_CMN_MSG_OFFSET,
+            summary_offset: Self::DEFAULT_SUMMARY_OFFSET,
+            probabilities: None,
+            exploded: None,
+            hint: None,
+            cursor: None,
+            pressed: None,
+            scroll: Point { x: 0, y: 0 },
+            view: Point { x: 0, y: 0 },
+            elapsed: Duration::ZERO,
+            summary: None,
+            theme: theme.clone(),
+            controls: keys.controls(),
+            reset_key: keys.reset.name(),
+        };
+    }
+
+    fn _print_message(&mut self, str: &str, p: &Point<usize>) {
+        self.term.color_bg(color::Reset);
+        self.term.color_fg(color::Reset);
+        self.term.clear_line(p.y);
+        let str = Self::_clip(str, Self::_line_width(p.x));
+        self.term.print(&str, p.x, p.y);
+    }
+
+    // x列目から行末までに書ける文字数。端末の大きさが分からなければ制限しない
+    fn _line_width(x: usize) -> usize {
+        return match termion::terminal_size() {
+            Ok((cols, _)) => (cols as usize).saturating_sub(x),
+            Err(_) => usize::MAX,
+        };
+    }
+
+    // 折り返して下の行を上書きしないよう、見える文字をwidth文字までに切り詰める
+    // 色を変えるエスケープシーケンスは幅に数えずにそのまま残す
+    fn _clip(str: &str, width: usize) -> String {
+        let mut clipped = String::new();
+        let mut visible = 0;
+        let mut chars = str.chars();
+        while let Some(c) = chars.next() {
+            if c == '\x1b' {
+                clipped.push(c);
+                for e in chars.by_ref() {
+                    clipped.push(e);
+                    if e.is_ascii_alphabetic() {
+                        break;
+                    }
+                }
+                continue;
+            }
+            if visible < width {
+                clipped.push(c);
+                visible += 1;
+            }
+        }
+        return clipped;
+    }
+
+    fn _undo_mark(ms: &Msweeper) -> &'static str {
+        if ms.is_undo_used() {
+            return " (undo used)";
+        }
+        return "";
+    }
+
+    fn _print_gameover(&mut self, ms: &Msweeper) {
+        self._print_message(
+            &format!(
+                "{}[Game Over]{} time: {:.3}s seed: {} hints: {}{} Press {} to reset.",
+                color::Bg(color::Red),
+                color::Bg(color::Reset),
+                self.elapsed.as_secs_f64(),
+                ms.seed(),
+                ms.hint_num(),
+                Self::_undo_mark(ms),
+                self.reset_key
+            ),
+            &self.cmn_msg_offset.clone(),
+        );
+    }
+
+    fn _print_gameclear(&mut self, ms: &Msweeper) {
+        self._print_message(
+            &format!(
+                "{}[Game Clear]{} time: {:.3}s seed: {} hints: {}{} Press {} to reset.",
+                color::Bg(color::Green),
+                color::Bg(color::Reset),
+                self.elapsed.as_secs_f64(),
+                ms.seed(),
+                ms.hint_num(),
+                Self::_undo_mark(ms),
+                self.reset_key
+            ),
+            &self.cmn_msg_offset.clone(),
+        );
+    }
+
+    // ヒントのマスを強調し、理由を表示する
+    pub fn print_hint(&mut self, ms: &Msweeper, hint: Option<Point<usize>>, message: &str) {
+        self.clear_hint(ms);
+        self._print_message(
+            &format!(
+                "{}[Hint]{} {}",
+                color::Bg(color::Blue),
+                color::Bg(color::Reset),
+                message
+            ),
+            &self.cmn_msg_offset.clone(),
+        );
+        if let Some(p) = &hint {
+            self.hint = hint.clone();
+            self._scroll_into_view(ms, p);
+            self._print_space(ms, p);
+            self.term.color_bg(color::Reset);
+        }
+    }
+
+    pub fn clear_hint(&mut self, ms: &Msweeper) {
+        if let Some(p) = self.hint.take() {
+            self._print_space(ms, &p);
+            self.term.color_bg(color::Reset);
+            self._print_message("", &self.cmn_msg_offset.clone());
+        }
+    }
+
+    fn _print_hint_space(&mut self, p: &Point<usize>) {
+        self.term.color_fg(color::Black);
+        self.term.color_bg(self.theme.hint_bg.ansi());
+        self._stdout_space("<>", p)
+    }
+
+    // 操作説明の行に状態を表示する
+    pub fn print_status(&mut self, str: &str) {
+        self._print_message(str, &self.controls_offset.clone());
+    }
+
+    fn _print_controls(&mut self) {
+        self._print_message(&self.controls.clone(), &self.controls_offset.clone());
+    }
+
+    // 終わったゲームの3BV、クリック数と効率を表示する。負けたときは開けた割合も出す
+    pub fn print_metrics(&mut self, m: &Metrics) {
+        let bbbv = if m.solved_bbbv == m.bbbv {
+            format!("{}", m.bbbv)
+        } else {
+            format!(
+                "{}/{} ({:.1}%)",
+                m.solved_bbbv,
+                m.bbbv,
+                m.solved_rate() * 100.0
+            )
+        };
+        let summary = format!(
+            "3BV: {}  3BV/s: {:.3}  clicks: {} (left {}, right {}, chord {}, flag chord {})  IOE: {:.3}",
+            bbbv,
+            m.bbbv_per_sec(),
+            m.clicks.total(),
+            m.clicks.left,
+            m.clicks.right,
+            m.clicks.chord,
+            m.clicks.flag_chord,
+            m.ioe()
+        );
+        self._print_message(&summary, &self.summary_offset.clone());
+        self.summary = Some(summary);
+    }
+
+    fn _clear_metrics(&mut self) {
+        if self.summary.take().is_some() {
+            self._print_message("", &self.summary_offset.clone());
+        }
+    }
+
+    pub fn set_elapsed(&mut self, elapsed: Duration) {
+        self.elapsed = elapsed;
+    }
+
+    // 盤面が端末に収まらなければ表示している範囲も出す
+    fn _print_remain(&mut self, ms: &Msweeper) {
+        let mut str = format!("REMAIN: {}  TIME: {}", ms.remain(), self.elapsed.as_secs());
+        if (self.view.x < ms.width()) || (self.view.y < ms.height()) {
+            str += &format!(
+                "  VIEW: x {}-{}/{}  y {}-{}/{}",
+                self.scroll.x + 1,
+                self.scroll.x + self.view.x,
+                ms.width(),
+                self.scroll.y + 1,
+                self.scroll.y + self.view.y,
+                ms.height()
+            );
+        }
+        // 配置を数えきれずに密度で近似しているとき
+        if self
+            .probabilities
+            .as_ref()
+            .is_some_and(|probs| !probs.is_exact())
+        {
+            str += "  PROB: estimated from the density";
+        }
+        self._print_message(&str, &self.remain_offset.clone());
+    }
+
+    // 端末の大きさが変わっていたら描き直す
+    pub fn flush(&mut self, ms: &Msweeper) {
+        if self._update_view(ms) {
+            self._repaint(ms);
+        }
+        self._print_remain(ms);
+        self.term.flush();
+    }
+
+    // 端末に収まるマスの数。端末の大きさが分からなければ盤面全体
+    fn _view_size(&self, ms: &Msweeper) -> Point<usize> {
+        let (width, height) = match termion::terminal_size() {
+            Ok((cols, rows)) => (
+                (cols as usize).saturating_sub(self.board_offset.x) / 2,
+                (rows as usize).saturating_sub(self.board_offset.y),
+            ),
+            Err(_) => (ms.width(), ms.height()),
+        };
+        return Point {
+            x: width.clamp(1, ms.width()),
+            y: height.clamp(1, ms.height()),
+        };
+    }
+
+    // 表示する範囲を端末の大きさに合わせる。変わったらtrue
+    fn _update_view(&mut self, ms: &Msweeper) -> bool {
+        let view = self._view_size(ms);
+        let scroll = Point {
+            x: self.scroll.x.min(ms.width() - view.x),
+            y: self.scroll.y.min(ms.height() - view.y),
+        };
+        let changed = !view.is_equal(&self.view) || !scroll.is_equal(&self.scroll);
+        self.view = view;
+        self.scroll = scroll;
+        return changed;
+    }
+
+    pub fn view_size(&self) -> Point<usize> {
+        return self.view.clone();
+    }
+
+    fn _is_visible(&self, p: &Point<usize>) -> bool {
+        return (p.x >= self.scroll.x)
+            && (p.x < self.scroll.x + self.view.x)
+            && (p.y >= self.scroll.y)
+            && (p.y < self.scroll.y + self.view.y);
+    }
+
+    fn _set_scroll(&mut self, ms: &Msweeper, scroll: Point<usize>) -> bool {
+        if scroll.is_equal(&self.scroll) {
+            return false;
+        }
+        self.scroll = scroll;
+        self.print_all_spaces(ms);
+        return true;
+    }
+
+    // 表示する範囲を動かす。盤面の外には出ない。動いたらtrue
+    pub fn scroll_by(&mut self, ms: &Msweeper, dx: isize, dy: isize) -> bool {
+        let max_x = (ms.width() - self.view.x) as isize;
+        let max_y = (ms.height() - self.view.y) as isize;
+        let scroll = Point {
+            x: (self.scroll.x as isize + dx).clamp(0, max_x) as usize,
+            y: (self.scroll.y as isize + dy).clamp(0, max_y) as usize,
+        };
+        return self._set_scroll(ms, scroll);
+    }
+
+    fn _scroll_into_view(&mut self, ms: &Msweeper, p: &Point<usize>) {
+        let mut scroll = self.scroll.clone();
+        if p.x < scroll.x {
+            scroll.x = p.x;
+        } else if p.x >= scroll.x + self.view.x {
+            scroll.x = p.x + 1 - self.view.x;
+        }
+        if p.y < scroll.y {
+            scroll.y = p.y;
+        } else if p.y >= scroll.y + self.view.y {
+            scroll.y = p.y + 1 - self.view.y;
+        }
+        self._set_scroll(ms, scroll);
+    }
+
+    // ドラッグしている位置が表示している範囲の端ならその方向に動かす
+    pub fn edge_scroll(&mut self, ms: &Msweeper, cursor_x: usize, cursor_y: usize) -> bool {
+        let col = (cursor_x as isize - (self.board_offset.x + 1) as isize).div_euclid(2);
+        let row = cursor_y as isize - (self.board_offset.y + 1) as isize;
+        let step = |pos: isize, size: usize| {
+            if pos <= 0 {
+                return -1;
+            }
+            if pos >= size as isize - 1 {
+                return 1;
+            }
+            return 0;
+        };
+        return self.scroll_by(ms, step(col, self.view.x), step(row, self.view.y));
+    }
+
+    // 表示している範囲の外のマスは描かない
+    fn _stdout_space(&mut self, print_str: &str, p: &Point<usize>) {
+        if !self._is_visible(p) {
+            return;
+        }
+        let is_cursor = self.cursor.as_ref().is_some_and(|c| c.is_equal(p));
+        if is_cursor {
+            self.term.style(style::Invert);
+        }
+        self.term.print(
+            print_str,
+            self.board_offset.x + (p.x - self.scroll.x) * 2,
+            self.board_offset.y + p.y - self.scroll.y,
+        );
+        if is_cursor {
+            self.term.style(style::NoInvert);
+        }
+    }
+
+    // カーソルを動かし、前の位置と新しい位置のマスを描き直す
+    pub fn set_cursor(&mut self, ms: &Msweeper, cursor: Option<Point<usize>>) {
+        let old = std::mem::replace(&mut self.cursor, cursor.clone());
+        if let Some(p) = &cursor {
+            self._scroll_into_view(ms, p);
+        }
+        for p in [old, cursor].iter().flatten() {
+            self._print_space(ms, p);
+        }
+        self.term.color_bg(color::Reset);
+    }
+
+    // 押しているマスとその周りの8マス
+    fn _around(ms: &Msweeper, p: &Point<usize>) -> Vec<Point<usize>> {
+        let p_i = p.utoi();
+        let mut rv = vec![p.clone()];
+        for pos8 in Point::pos8_iter() {
+            let n = p_i.get_pos_8(pos8);
+            if (n.x >= 0)
+                && (n.y >= 0)
+                && ((n.x as usize) < ms.width())
+                && ((n.y as usize) < ms.height())
+            {
+                rv.push(n.itou());
+            }
+        }
+        return rv;
+    }
+
+    fn _is_pressed(&self, p: &Point<usize>) -> bool {
+        let Some(c) = &self.pressed else {
+            return false;
+        };
+        return (c.x.abs_diff(p.x) <= 1) && (c.y.abs_diff(p.y) <= 1);
+    }
+
+    // 同時押しのプレビューを動かし、前と今の3x3を描き直す
+    pub fn set_pressed(&mut self, ms: &Msweeper, pressed: Option<Point<usize>>) {
+        let old = std::mem::replace(&mut self.pressed, pressed.clone());
+        for c in [old, pressed].iter().flatten() {
+            for p in Self::_around(ms, c) {
+                self._print_space(ms, &p);
+            }
+        }
+        self.term.color_bg(color::Reset);
+    }
+
+    fn _print_pressed(&mut self, p: &Point<usize>) {
+        self.term.color_fg(color::Reset);
+        self.term.color_bg(self.theme.opened_bg.ansi());
+        self._stdout_space("  ", p)
+    }
+
+    fn _print_empty(&mut self, ms: &Msweeper, p: &Point<usize>) {
+        let mnum = ms.mine_count(p);
+        self.term.color_bg(self.theme.opened_bg.ansi());
+        let str = match mnum {
+            0 => {
+                self.term.color_fg(color::Reset);
+                "  ".to_string()
+            }
+            n if (n > 0 && n <= 8) => {
+                self.term.color_fg(self.theme.numbers[n - 1].ansi());
+                format!("{} ", mnum)
+            }
+            _ => "??".to_string(),
+        };
+        self._stdout_space(&str, p);
+    }
+
+    fn _print_unopened(&mut self, p: &Point<usize>) {
+        self.term.color_fg(self.theme.unopened_fg.ansi());
+        self.term.color_bg(self.theme.unopened_bg.ansi());
+        self._stdout_space("_|", p)
+    }
+
+    // 地雷の確率を百分率で表示する。確実に安全なら0、確実に地雷なら!!
+    fn _print_probability(&mut self, p: &Point<usize>, prob: f64) {
+        self.term.color_bg(self.theme.unopened_bg.ansi());
+        let str = if prob <= 0.0 {
+            self.term.color_fg(color::LightGreen);
+            " 0".to_string()
+        } else if prob >= 1.0 {
+            self.term.color_fg(color::LightRed);
+            "!!".to_string()
+        } else {
+            self.term.color_fg(color::LightYellow);
+            format!("{:2}", ((prob * 100.0).round() as usize).clamp(1, 99))
+        };
+        self._stdout_space(&str, p)
+    }
+
+    pub fn set_probabilities(&mut self, probabilities: Option<Probabilities>) {
+        self.probabilities = probabilities;
+    }
+
+    fn _print_flag(&mut self, p: &Point<usize>) {
+        self.term.color_fg(self.theme.flag_fg.ansi());
+        self.term.color_bg(self.theme.unopened_bg.ansi());
+        let unopened_fg = self.theme.unopened_fg.ansi();
+        self._stdout_space(&format!("P{}|", color::Fg(unopened_fg)), p)
+    }
+
+    fn _print_question(&mut self, p: &Point<usize>) {
+        self.term.color_fg(self.theme.question_fg.ansi());
+        self.term.color_bg(self.theme.unopened_bg.ansi());
+        let unopened_fg = self.theme.unopened_fg.ansi();
+        self._stdout_space(&format!("?{}|", color::Fg(unopened_fg)), p)
+    }
+
+    fn _print_mine(&mut self, p: &Point<usize>) {
+        self.term.color_fg(self.theme.mine_fg.ansi());
+        self.term.color_bg(self.theme.opened_bg.ansi());
+        self._stdout_space("* ", p)
+    }
+
+    fn _print_pressed_mine(&mut self, p: &Point<usize>) {
+        self.term.color_fg(self.theme.mine_fg.ansi());
+        self.term.color_bg(self.theme.exploded_bg.ansi());
+        self._stdout_space("* ", p)
+    }
+
+    fn _print_space(&mut self, ms: &Msweeper, p: &Point<usize>) {
+        let space = ms.space(p);
+        if self.hint.as_ref().is_some_and(|h| h.is_equal(p))
+            && matches!(space.state, SpaceState::UNOPENED | SpaceState::QUESTION)
+        {
+            self._print_hint_space(p);
+            return;
+        }
+        match space.state {
+            SpaceState::UNOPENED | SpaceState::QUESTION if self._is_pressed(p) => {
+                self._print_pressed(p)
+            }
+            SpaceState::UNOPENED => {
+                match self.probabilities.as_ref().and_then(|probs| probs.get(p)) {
+                    Some(prob) => self._print_probability(p, prob),
+                    None => self._print_unopened(p),
+                }
+            }
+            SpaceState::OPENED => match space.stype {
+                SpaceType::MINE => match &self.exploded {
+                    Some(e) if e.is_equal(p) => self._print_pressed_mine(p),
+                    _ => self._print_mine(p),
+                },
+                SpaceType::EMPTY => self._print_empty(ms, p),
+            },
+            SpaceState::FLAG => self._print_flag(p),
+            SpaceState::QUESTION => self._print_question(p),
+        };
+    }
+
+    pub fn print_all_spaces(&mut self, ms: &Msweeper) {
+        for _y in self.scroll.y..(self.scroll.y + self.view.y) {
+            for _x in self.scroll.x..(self.scroll.x + self.view.x) {
+                self._print_space(ms, &Point::<usize> { x: _x, y: _y });
+            }
+        }
+        self.term.color_bg(color::Reset);
+    }
+
+    // 画面全体を描き直す
+    pub fn redraw(&mut self, ms: &Msweeper) {
+        self.exploded = None;
+        self.hint = None;
+        self.summary = None;
+        self._update_view(ms);
+        self.term.clear();
+        self._print_controls();
+        self.print_all_spaces(ms);
+        self.flush(ms);
+    }
+
+    // 統計などの画面を盤面の代わりに表示する
+    pub fn print_screen(&mut self, title: &str, lines: &[String]) {
+        self.term.clear();
+        self._print_message(title, &self.controls_offset.clone());
+        let width = Self::_line_width(self.board_offset.x);
+        for (i, line) in lines.iter().enumerate() {
+            self.term.print(
+                &Self::_clip(line, width),
+                self.board_offset.x,
+                self.cmn_msg_offset.y + 1 + i,
+            );
+        }
+        self.term.flush();
+    }
+
+    // 別の画面から盤面に戻る。踏んだ地雷と終わったときの表示は残す
+    pub fn restore(&mut self, ms: &Msweeper) {
+        self.hint = None;
+        self._update_view(ms);
+        self._repaint(ms);
+        self.flush(ms);
+    }
+
+    fn _repaint(&mut self, ms: &Msweeper) {
+        self.term.clear();
+        self._print_controls();
+        self.print_all_spaces(ms);
+        if ms.is_finished() {
+            if ms.is_clear() {
+                self._print_gameclear(ms);
+            } else {
+                self._print_gameover(ms);
+            }
+        }
+        if let Some(summary) = self.summary.clone() {
+            self._print_message(&summary, &self.summary_offset.clone());
+        }
+    }
+
+    // Msweeperが返した変化を画面に反映する
+    pub fn apply(&mut self, ms: &Msweeper, changes: &[MSChange]) {
+        for change in changes {
+            match change {
+                MSChange::Space(p) => self._print_space(ms, p),
+                MSChange::Exploded(p) => {
+                    self.exploded = Some(p.clone());
+                    self._print_pressed_mine(p);
+                }
+                MSChange::GameOver => self._print_gameover(ms),
+                MSChange::GameClear => self._print_gameclear(ms),
+                MSChange::NoGuessFailed => self._print_message(
+                    &format!(
+                        "{}[No Guess]{} Could not make a board that can be solved without guessing in time. Click again to keep looking, or use fewer mines.",
+                        color::Bg(color::Red),
+                        color::Bg(color::Reset)
+                    ),
+                    &self.cmn_msg_offset.clone(),
+                ),
+                MSChange::Resumed => {
+                    self.exploded = None;
+                    self._print_message("", &self.cmn_msg_offset.clone());
+                    self._clear_metrics();
+                }
+            }
+        }
+        self.term.color_bg(color::Reset);
+    }
+
+    fn _get_press_pos(&self, cursor_x: usize, cursor_y: usize) -> Option<Point<usize>> {
+        return Self::_to_board_pos(
+            &self.board_offset,
+            &self.scroll,
+            &self.view,
+            cursor_x,
+            cursor_y,
+        );
+    }
+
+    // 1始まりの画面上の座標を、表示している範囲の左上のマスscrollからの盤面上の座標にする
+    fn _to_board_pos(
+        board_offset: &Point<usize>,
+        scroll: &Point<usize>,
+        view: &Point<usize>,
+        cursor_x: usize,
+        cursor_y: usize,
+    ) -> Option<Point<usize>> {
+        let b_x = board_offset.x + 1;
+        let b_y = board_offset.y + 1;
+        if (cursor_x < (b_x)) || (cursor_y < b_y) {
+            return None;
+        }
+        let (x, y) = ((cursor_x - b_x) / 2, cursor_y - b_y);
+        if (x >= view.x) || (y >= view.y) {
+            return None;
+        }
+        return Some(Point::<usize> {
+            x: scroll.x + x,
+            y: scroll.y + y,
+        });
+    }
+
+    // 画面上の座標を盤面上の座標に変換する
+    pub fn get_board_press_pos(
+        &self,
+        ms: &Msweeper,
+        cursor_x: usize,
+        cursor_y: usize,
+    ) -> Option<Point<usize>> {
+        let p = self._get_press_pos(cursor_x, cursor_y)?;
+        if (p.x >= ms.width()) || (p.y >= ms.height()) {
+            return None;
+        }
+        return Some(p);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clip_counts_only_visible_characters() {
+        let banner = format!(
+            "{}[Hint]{} (3,1) is safe.",
+            color::Bg(color::Blue),
+            color::Bg(color::Reset)
+        );
+        assert_eq!(
+            Renderer::_clip(&banner, 8),
+            format!(
+                "{}[Hint]{} (",
+                color::Bg(color::Blue),
+                color::Bg(color::Reset)
+            )
+        );
+        assert_eq!(Renderer::_clip(&banner, 100), banner);
+        assert_eq!(Renderer::_clip("REMAIN: 10", 3), "REM");
+    }
+
+    #[test]
+    fn press_position_goes_through_the_scroll_offset() {
+        let offset = Renderer::DEFAULT_BOARD_OFFSET;
+        let view = Point { x: 9, y: 9 };
+        let pos = |scroll: &Point<usize>, x: usize, y: usize| {
+            Renderer::_to_board_pos(&offset, scroll, &view, x, y).map(|p| (p.x, p.y))
+        };
+        let top_left = Point { x: 0, y: 0 };
+        // 1マスは2列
+        assert_eq!(pos(&top_left, 3, 7), Some((0, 0)));
+        assert_eq!(pos(&top_left, 4, 7), Some((0, 0)));
+        assert_eq!(pos(&top_left, 5, 7), Some((1, 0)));
+        assert_eq!(pos(&top_left, 2, 7), None);
+
+        let scrolled = Point { x: 10, y: 4 };
+        assert_eq!(pos(&scrolled, 3, 7), Some((10, 4)));
+        assert_eq!(pos(&scrolled, 19, 15), Some((18, 12)));
+        // 表示している範囲の外
+        assert_eq!(pos(&scrolled, 21, 15), None);
+        assert_eq!(pos(&scrolled, 3, 16), None);
+    }
+}
@@ -0,0 +1,211 @@
+use crate::msweeper::{MSChange, Msweeper};
+use crate::point::Point;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+// 記録する入力の種類
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    Open,
+    Flag,
+    // 開いている数字のマスを開いて周囲8マスを開く
+    Chord,
+    // 開いている数字のマスの周囲8マスに旗を立てる
+    FlagChord,
+    Undo,
+    Redo,
+    Reset,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Input {
+    // ゲーム開始からの経過時間(ミリ秒)
+    pub time_ms: u64,
+    pub action: Action,
+    pub x: usize,
+    pub y: usize,
+}
+
+impl Input {
+    pub fn pos(&self) -> Point<usize> {
+        return Point::<usize> {
+            x: self.x,
+            y: self.y,
+        };
+    }
+
+    // 盤面に入力を適用する。やり直しは時計なども戻すので呼び出し側で扱う
+    pub fn apply(&self, ms: &mut Msweeper) -> Vec<MSChange> {
+        let p = self.pos();
+        return match self.action {
+            Action::Open => ms.open(&p),
+            Action::Chord => ms.chord(&p),
+            Action::Flag => ms.flag(&p),
+            Action::FlagChord => ms.flag_chord(&p),
+            Action::Undo => ms.undo(),
+            Action::Redo => ms.redo(),
+            Action::Reset => Vec::new(),
+        };
+    }
+}
+
+fn _first_version() -> u32 {
+    return 1;
+}
+
+// 1ゲーム分の盤面と入力の記録
+#[derive(Serialize, Deserialize)]
+pub struct Replay {
+    // ファイルの形式の版。版を付ける前のファイルは1として読む
+    #[serde(default = "_first_version")]
+    pub version: u32,
+    pub width: usize,
+    pub height: usize,
+    pub mine_num: usize,
+    pub seed: u64,
+    // 1行ごとの地雷の配置。'*'が地雷、'.'が安全なマス
+    pub mines: Vec<String>,
+    // 開いたマスや旗がある盤面から始めたときの最初の盤面。boardモジュールの形式
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub board: Vec<String>,
+    // 旗の次に?の印を付けていたか。再生で同じ状態をたどるのに使う
+    #[serde(default)]
+    pub question_marks: bool,
+    pub inputs: Vec<Input>,
+}
+
+impl Replay {
+    // 形式を変えたら上げる。読めない版のファイルはエラーにする
+    pub const VERSION: u32 = 1;
+
+    pub fn construct(ms: &Msweeper, board: Vec<String>, inputs: Vec<Input>) -> Replay {
+        let mines = ms
+            .mine_layout()
+            .iter()
+            .map(|row| row.iter().map(|&m| if m { '*' } else { '.' }).collect())
+            .collect();
+        return Replay {
+            version: Self::VERSION,
+            width: ms.width(),
+            height: ms.height(),
+            mine_num: ms.mine_num(),
+            seed: ms.seed(),
+            mines,
+            board,
+            question_marks: ms.is_question_marks(),
+            inputs,
+        };
+    }
+
+    pub fn mine_layout(&self) -> Vec<Vec<bool>> {
+        return self
+            .mines
+            .iter()
+            .map(|row| row.chars().map(|c| c == '*').collect())
+            .collect();
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).map_err(|e| format!("{}: {}", dir.display(), e))?;
+        }
+        fs::write(path, self.to_json()?).map_err(|e| format!("{}: {}", path.display(), e))?;
+        return Ok(());
+    }
+
+    pub fn load(path: &Path) -> Result<Replay, String> {
+        let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        return Self::from_json(&text).map_err(|e| format!("{}: {}", path.display(), e));
+    }
+
+    pub fn to_json(&self) -> Result<String, String> {
+        return serde_json::to_string_pretty(self).map_err(|e| e.to_string());
+    }
+
+    pub fn from_json(text: &str) -> Result<Replay, String> {
+        let replay: Replay = serde_json::from_str(text).map_err(|e| e.to_string())?;
+        if replay.version != Self::VERSION {
+            return Err(format!(
+                "unsupported replay version {}; this build reads version {}",
+                replay.version,
+                Self::VERSION
+            ));
+        }
+        return Ok(replay);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board;
+
+    fn play(ms: &mut Msweeper, inputs: &mut Vec<Input>, action: Action, x: usize, y: usize) {
+        let input = Input {
+            time_ms: inputs.len() as u64 * 100,
+            action,
+            x,
+            y,
+        };
+        input.apply(ms);
+        inputs.push(input);
+    }
+
+    #[test]
+    fn recorded_game_replays_to_the_same_board() {
+        let mut ms = Msweeper::construct(9, 9, 10, Some(4)).unwrap();
+        let mut inputs = Vec::new();
+        play(&mut ms, &mut inputs, Action::Open, 4, 4);
+        let layout = ms.mine_layout();
+        let (mine_y, mine_x) = (0..81)
+            .map(|i| (i / 9, i % 9))
+            .find(|&(y, x)| layout[y][x])
+            .unwrap();
+        play(&mut ms, &mut inputs, Action::Flag, mine_x, mine_y);
+        play(&mut ms, &mut inputs, Action::Undo, 0, 0);
+        play(&mut ms, &mut inputs, Action::Redo, 0, 0);
+        for y in 0..9 {
+            for x in 0..9 {
+                if !layout[y][x] {
+                    play(&mut ms, &mut inputs, Action::Open, x, y);
+                }
+            }
+        }
+        assert!(ms.is_clear());
+
+        let json = Replay::construct(&ms, Vec::new(), inputs)
+            .to_json()
+            .unwrap();
+        let replay = Replay::from_json(&json).unwrap();
+        let mut replayed = Msweeper::construct(
+            replay.width,
+            replay.height,
+            replay.mine_num,
+            Some(replay.seed),
+        )
+        .unwrap();
+        replayed.set_mine_layout(&replay.mine_layout()).unwrap();
+        for input in &replay.inputs {
+            input.apply(&mut replayed);
+        }
+        assert!(replayed.is_clear());
+        assert_eq!(board::format(&replayed), board::format(&ms));
+    }
+
+    #[test]
+    fn bad_files_are_rejected() {
+        let ms = Msweeper::construct(2, 2, 1, Some(0)).unwrap();
+        let json = Replay::construct(&ms, Vec::new(), Vec::new())
+            .to_json()
+            .unwrap();
+        let future = json.replace("\"version\": 1", "\"version\": 2");
+        assert_eq!(
+            Replay::from_json(&future).err().unwrap(),
+            "unsupported replay version 2; this build reads version 1"
+        );
+        assert!(Replay::from_json("{\"width\": 2").is_err());
+        assert!(Replay::from_json("[]").is_err());
+    }
+}
@@ -0,0 +1,52 @@
+use crate::msweeper::MsweeperState;
+use crate::replay::Input;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// 中断したゲームの保存データ
+#[derive(Serialize, Deserialize)]
+pub struct SaveData {
+    pub msweeper: MsweeperState,
+    // 最初に開いてからの経過時間(ミリ秒)
+    pub elapsed_ms: u64,
+    // リプレイを続けて記録するための入力と記録開始からの時間
+    pub record_ms: u64,
+    pub inputs: Vec<Input>,
+    // 読み込んだ盤面から始めたときの最初の盤面
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub start_board: Vec<String>,
+}
+
+// スロット名はファイル名にそのまま使うので英数字と'-'、'_'に限る
+pub fn slot_path(dir: &Path, slot: &str) -> Result<PathBuf, String> {
+    if slot.is_empty()
+        || !slot
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        return Err(format!(
+            "Invalid slot name '{}'. Use letters, digits, '-' and '_'.",
+            slot
+        ));
+    }
+    return Ok(dir.join(format!("{}.json", slot)));
+}
+
+impl SaveData {
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).map_err(|e| format!("{}: {}", dir.display(), e))?;
+        }
+        let text = serde_json::to_string(self).map_err(|e| e.to_string())?;
+        fs::write(path, text).map_err(|e| format!("{}: {}", path.display(), e))?;
+        return Ok(());
+    }
+
+    pub fn load(path: &Path) -> Result<SaveData, String> {
+        let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let data: SaveData =
+            serde_json::from_str(&text).map_err(|e| format!("{}: {}", path.display(), e))?;
+        return Ok(data);
+    }
+}
@@ -0,0 +1,336 @@
+use crate::msweeper::{Msweeper, SpaceState, SpaceType};
+use crate::point::Point;
+
+// プレイヤーから見えている盤面の1マス
+#[derive(Clone, PartialEq)]
+pub enum Cell {
+    Unknown,
+    Mine,
+    // 開いているマスと周囲の地雷数
+    Number(usize),
+}
+
+// なぜそのマスが決まったか
+#[derive(Clone)]
+pub enum Reason {
+    // この数字1つだけで決まる
+    Single(Point<usize>),
+    // 1つ目の数字の未開封マスと2つ目の数字の未開封マスの包含関係で決まる
+    Pair(Point<usize>, Point<usize>),
+    // 残りの地雷数で決まる
+    MineCount,
+}
+
+pub struct Deduction {
+    pub pos: Point<usize>,
+    pub reason: Reason,
+}
+
+// 推論の結果
+pub struct Deductions {
+    pub safe: Vec<Deduction>,
+    pub mines: Vec<Deduction>,
+}
+
+impl Deductions {
+    pub fn is_empty(&self) -> bool {
+        return self.safe.is_empty() && self.mines.is_empty();
+    }
+}
+
+type Reasons = Vec<Option<Reason>>;
+
+// 数字1つ分の制約。unknownのうちちょうどmines個が地雷
+struct Constraint {
+    center: usize,
+    unknown: Vec<usize>,
+    mines: usize,
+}
+
+// 見えている情報だけを使って確実に安全なマスと確実に地雷のマスを求める
+// 旗は正しく立てられているものとして扱う
+pub struct Solver {
+    width: usize,
+    height: usize,
+    mine_num: usize,
+    cells: Vec<Cell>,
+}
+
+impl Solver {
+    pub fn construct(width: usize, height: usize, mine_num: usize) -> Solver {
+        return Solver {
+            width,
+            height,
+            mine_num,
+            cells: vec![Cell::Unknown; width * height],
+        };
+    }
+
+    // Msweeperの盤面のうちプレイヤーに見えている情報だけを取り込む
+    pub fn from_msweeper(ms: &Msweeper) -> Solver {
+        let mut solver = Self::construct(ms.width(), ms.height(), ms.mine_num());
+        for y in 0..ms.height() {
+            for x in 0..ms.width() {
+                let p = Point::<usize> { x, y };
+                let space = ms.space(&p);
+                let cell = match (&space.state, &space.stype) {
+                    (SpaceState::UNOPENED | SpaceState::QUESTION, _) => Cell::Unknown,
+                    (SpaceState::FLAG, _) => Cell::Mine,
+                    (SpaceState::OPENED, SpaceType::MINE) => Cell::Mine,
+                    (SpaceState::OPENED, SpaceType::EMPTY) => Cell::Number(ms.mine_count(&p)),
+                };
+                solver.set(&p, cell);
+            }
+        }
+        return solver;
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn mine_num(&self) -> usize {
+        self.mine_num
+    }
+
+    pub fn cell(&self, p: &Point<usize>) -> &Cell {
+        &self.cells[p.y * self.width + p.x]
+    }
+
+    pub fn set(&mut self, p: &Point<usize>, cell: Cell) {
+        self.cells[p.y * self.width + p.x] = cell;
+    }
+
+    fn _to_point(&self, idx: usize) -> Point<usize> {
+        return Point::<usize> {
+            x: idx % self.width,
+            y: idx / self.width,
+        };
+    }
+
+    fn _neighbors(&self, idx: usize) -> Vec<usize> {
+        let p = self._to_point(idx).utoi();
+        let mut rv = Vec::new();
+        for pos8 in Point::pos8_iter() {
+            let n = p.get_pos_8(pos8);
+            if (n.x < 0)
+                || (n.y < 0)
+                || (n.x as usize >= self.width)
+                || (n.y as usize >= self.height)
+            {
+                continue;
+            }
+            rv.push(n.y as usize * self.width + n.x as usize);
+        }
+        return rv;
+    }
+
+    // 2マス以内の数字同士でなければ未開封のマスを共有しない
+    fn _is_near(&self, a: usize, b: usize) -> bool {
+        let pa = self._to_point(a);
+        let pb = self._to_point(b);
+        return pa.x.abs_diff(pb.x) <= 2 && pa.y.abs_diff(pb.y) <= 2;
+    }
+
+    fn _constraints(&self) -> Vec<Constraint> {
+        let mut rv = Vec::new();
+        for idx in 0..self.cells.len() {
+            let num = match self.cells[idx] {
+                Cell::Number(n) => n,
+                _ => continue,
+            };
+            let mut unknown = Vec::new();
+            let mut known_mines = 0;
+            for n in self._neighbors(idx) {
+                match self.cells[n] {
+                    Cell::Unknown => unknown.push(n),
+                    Cell::Mine => known_mines += 1,
+                    Cell::Number(_) => {}
+                }
+            }
+            if unknown.is_empty() || known_mines > num {
+                continue;
+            }
+            rv.push(Constraint {
+                center: idx,
+                unknown,
+                mines: num - known_mines,
+            });
+        }
+        return rv;
+    }
+
+    // 1つの数字だけで決まるマス
+    fn _single(&self, constraints: &[Constraint], safe: &mut Reasons, mines: &mut Reasons) {
+        for c in constraints {
+            let reason = Reason::Single(self._to_point(c.center));
+            if c.mines == 0 {
+                for &idx in &c.unknown {
+                    Self::_found(safe, idx, &reason);
+                }
+            } else if c.mines == c.unknown.len() {
+                for &idx in &c.unknown {
+                    Self::_found(mines, idx, &reason);
+                }
+            }
+        }
+    }
+
+    // 2つの数字の包含関係から決まるマス
+    // Bだけに含まれるマスの数がBとAの地雷数の差と等しければ、
+    // Bだけのマスはすべて地雷でAだけのマスはすべて安全
+    fn _pair(&self, constraints: &[Constraint], safe: &mut Reasons, mines: &mut Reasons) {
+        for (i, a) in constraints.iter().enumerate() {
+            for (j, b) in constraints.iter().enumerate() {
+                if (i == j) || (b.mines < a.mines) || !self._is_near(a.center, b.center) {
+                    continue;
+                }
+                if !b.unknown.iter().any(|idx| a.unknown.contains(idx)) {
+                    continue;
+                }
+                let only_b: Vec<usize> = b
+                    .unknown
+                    .iter()
+                    .filter(|idx| !a.unknown.contains(idx))
+                    .cloned()
+                    .collect();
+                if b.mines - a.mines != only_b.len() {
+                    continue;
+                }
+                let reason = Reason::Pair(self._to_point(a.center), self._to_point(b.center));
+                for idx in only_b {
+                    Self::_found(mines, idx, &reason);
+                }
+                for &idx in a.unknown.iter().filter(|idx| !b.unknown.contains(idx)) {
+                    Self::_found(safe, idx, &reason);
+                }
+            }
+        }
+    }
+
+    // 残りの地雷数から決まるマス
+    fn _global(&self, safe: &mut Reasons, mines: &mut Reasons) {
+        let known_mines = self.cells.iter().filter(|c| **c == Cell::Mine).count();
+        let unknown: Vec<usize> = (0..self.cells.len())
+            .filter(|&idx| self.cells[idx] == Cell::Unknown)
+            .collect();
+        if known_mines > self.mine_num || unknown.is_empty() {
+            return;
+        }
+        let remain = self.mine_num - known_mines;
+        if remain == 0 {
+            for idx in unknown {
+                Self::_found(safe, idx, &Reason::MineCount);
+            }
+        } else if remain == unknown.len() {
+            for idx in unknown {
+                Self::_found(mines, idx, &Reason::MineCount);
+            }
+        }
+    }
+
+    // 最初に見つかった理由だけを残す
+    fn _found(reasons: &mut Reasons, idx: usize, reason: &Reason) {
+        if reasons[idx].is_none() {
+            reasons[idx] = Some(reason.clone());
+        }
+    }
+
+    // 確実に安全なマスと確実に地雷のマスを、簡単な理由で決まるものから順に求める
+    pub fn deduce(&self) -> Deductions {
+        let constraints = self._constraints();
+        let mut safe: Reasons = vec![None; self.cells.len()];
+        let mut mines: Reasons = vec![None; self.cells.len()];
+
+        self._single(&constraints, &mut safe, &mut mines);
+        self._pair(&constraints, &mut safe, &mut mines);
+        self._global(&mut safe, &mut mines);
+
+        let mut rv = Deductions {
+            safe: Vec::new(),
+            mines: Vec::new(),
+        };
+        for idx in 0..self.cells.len() {
+            if let Some(reason) = safe[idx].take() {
+                rv.safe.push(Deduction {
+                    pos: self._to_point(idx),
+                    reason,
+                });
+            } else if let Some(reason) = mines[idx].take() {
+                rv.mines.push(Deduction {
+                    pos: self._to_point(idx),
+                    reason,
+                });
+            }
+        }
+        return rv;
+    }
+}
+
+// テスト用に文字列から盤面を作る。'.'は未開封、'F'は旗、数字は開いたマス
+#[cfg(test)]
+impl Solver {
+    pub(crate) fn from_rows(rows: &[&str], mine_num: usize) -> Solver {
+        let mut solver = Solver::construct(rows[0].len(), rows.len(), mine_num);
+        for (y, row) in rows.iter().enumerate() {
+            for (x, c) in row.chars().enumerate() {
+                let cell = match c {
+                    '.' => Cell::Unknown,
+                    'F' => Cell::Mine,
+                    n => Cell::Number(n.to_digit(10).unwrap() as usize),
+                };
+                solver.set(&Point { x, y }, cell);
+            }
+        }
+        return solver;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn positions(deductions: &[Deduction]) -> Vec<(usize, usize)> {
+        return deductions.iter().map(|d| (d.pos.x, d.pos.y)).collect();
+    }
+
+    #[test]
+    fn single_number_touching_its_flag() {
+        let solver = Solver::from_rows(&["F..", "1..", "0.."], 5);
+        let d = solver.deduce();
+        assert_eq!(positions(&d.safe), vec![(1, 0), (1, 1), (1, 2)]);
+        assert!(d.mines.is_empty());
+    }
+
+    #[test]
+    fn one_two_one_pattern() {
+        let mut solver = Solver::from_rows(&["...", "121", "000"], 10);
+        let d = solver.deduce();
+        assert_eq!(positions(&d.mines), vec![(0, 0), (2, 0)]);
+        assert!(matches!(d.mines[0].reason, Reason::Pair(_, _)));
+        assert!(d.safe.is_empty());
+
+        for m in &d.mines {
+            solver.set(&m.pos, Cell::Mine);
+        }
+        assert_eq!(positions(&solver.deduce().safe), vec![(1, 0)]);
+    }
+
+    #[test]
+    fn mine_count_decides_the_rest() {
+        let solver = Solver::from_rows(&["F.", "1."], 1);
+        let d = solver.deduce();
+        assert_eq!(positions(&d.safe), vec![(1, 0), (1, 1)]);
+        assert!(matches!(d.safe[0].reason, Reason::Single(_)));
+    }
+
+    #[test]
+    fn no_deduction_for_fifty_fifty() {
+        let solver = Solver::from_rows(&["..", "11", "00"], 1);
+        assert!(solver.deduce().is_empty());
+    }
+}
@@ -0,0 +1,182 @@
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+// 終わったゲーム1つ分の記録
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Record {
+    pub width: usize,
+    pub height: usize,
+    pub mine_num: usize,
+    pub won: bool,
+    // 最初に開いてからの時間(ミリ秒)
+    pub time_ms: u64,
+    // 終わった日時(UNIX時間の秒)
+    pub date: u64,
+    pub seed: u64,
+}
+
+// 盤面の大きさと地雷数ごとの集計
+pub struct Summary {
+    pub width: usize,
+    pub height: usize,
+    pub mine_num: usize,
+    pub played: usize,
+    pub won: usize,
+    // 最後の試合から数えた連勝数
+    pub current_streak: usize,
+    pub best_streak: usize,
+    pub total_win_ms: u64,
+}
+
+impl Summary {
+    pub fn win_rate(&self) -> f64 {
+        return self.won as f64 / self.played as f64;
+    }
+
+    pub fn average_win_ms(&self) -> Option<u64> {
+        if self.won == 0 {
+            return None;
+        }
+        return Some(self.total_win_ms / self.won as u64);
+    }
+}
+
+pub fn stats_path(data_dir: &Path) -> PathBuf {
+    return data_dir.join("stats.jsonl");
+}
+
+// 1行に1つの記録を追記する
+pub fn append(path: &Path, record: &Record) -> Result<(), String> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|e| format!("{}: {}", dir.display(), e))?;
+    }
+    let line = serde_json::to_string(record).map_err(|e| e.to_string())?;
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .map_err(|e| format!("{}: {}", path.display(), e))?;
+    writeln!(file, "{}", line).map_err(|e| format!("{}: {}", path.display(), e))?;
+    return Ok(());
+}
+
+// まだ記録がなければ空
+pub fn load(path: &Path) -> Result<Vec<Record>, String> {
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    let mut rv = Vec::new();
+    for (i, line) in text.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let record = serde_json::from_str(line)
+            .map_err(|e| format!("{}: line {}: {}", path.display(), i + 1, e))?;
+        rv.push(record);
+    }
+    return Ok(rv);
+}
+
+// 記録の順に大きさと地雷数ごとに集計する
+pub fn summarize(records: &[Record]) -> Vec<Summary> {
+    let mut rv: Vec<Summary> = Vec::new();
+    for r in records {
+        let index = match rv
+            .iter()
+            .position(|s| (s.width, s.height, s.mine_num) == (r.width, r.height, r.mine_num))
+        {
+            Some(index) => index,
+            None => {
+                rv.push(Summary {
+                    width: r.width,
+                    height: r.height,
+                    mine_num: r.mine_num,
+                    played: 0,
+                    won: 0,
+                    current_streak: 0,
+                    best_streak: 0,
+                    total_win_ms: 0,
+                });
+                rv.len() - 1
+            }
+        };
+        let s = &mut rv[index];
+        s.played += 1;
+        if r.won {
+            s.won += 1;
+            s.total_win_ms += r.time_ms;
+            s.current_streak += 1;
+            s.best_streak = s.best_streak.max(s.current_streak);
+        } else {
+            s.current_streak = 0;
+        }
+    }
+    rv.sort_by_key(|s| (s.width * s.height, s.mine_num, s.width));
+    return rv;
+}
+
+// 統計画面と--statsで使う表
+pub fn format_table(summaries: &[Summary]) -> Vec<String> {
+    if summaries.is_empty() {
+        return vec!["No games recorded yet.".to_string()];
+    }
+    let mut rv = vec![format!(
+        "{:>7} {:>5} {:>6} {:>6} {:>6} {:>4} {:>9}",
+        "size", "mines", "played", "win%", "streak", "best", "avg win"
+    )];
+    for s in summaries {
+        let average = match s.average_win_ms() {
+            Some(ms) => format!("{:.3}s", ms as f64 / 1000.0),
+            None => "-".to_string(),
+        };
+        rv.push(format!(
+            "{:>7} {:>5} {:>6} {:>5.1}% {:>6} {:>4} {:>9}",
+            format!("{}x{}", s.width, s.height),
+            s.mine_num,
+            s.played,
+            s.win_rate() * 100.0,
+            s.current_streak,
+            s.best_streak,
+            average
+        ));
+    }
+    return rv;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(width: usize, won: bool, time_ms: u64) -> Record {
+        return Record {
+            width,
+            height: 9,
+            mine_num: 10,
+            won,
+            time_ms,
+            date: 0,
+            seed: 0,
+        };
+    }
+
+    #[test]
+    fn summary_tracks_streaks_per_configuration() {
+        let records = vec![
+            record(9, true, 1000),
+            record(9, true, 3000),
+            record(16, true, 5000),
+            record(9, false, 0),
+            record(9, true, 2000),
+        ];
+        let summaries = summarize(&records);
+        assert_eq!(summaries.len(), 2);
+        let s = &summaries[0];
+        assert_eq!((s.width, s.played, s.won), (9, 4, 3));
+        assert_eq!((s.current_streak, s.best_streak), (1, 2));
+        assert_eq!(s.average_win_ms(), Some(2000));
+        assert_eq!(summaries[1].average_win_ms(), Some(5000));
+    }
+}
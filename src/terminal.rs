@@ -74,6 +74,10 @@ impl Terminal {
         write!(self.stdout, "{}", color::Fg(color)).unwrap();
     }
 
+    pub fn style<S: std::fmt::Display>(&mut self, style: S) {
+        write!(self.stdout, "{}", style).unwrap();
+    }
+
     pub fn flush(&mut self) {
         self.stdout.flush().unwrap();
     }
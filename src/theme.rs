@@ -0,0 +1,147 @@
+use serde::de::{self, Deserializer, Visitor};
+use serde::Deserialize;
+use std::fmt;
+use termion::color::AnsiValue;
+
+// 端末の256色の番号。設定ファイルでは名前か0から255の数で指定する
+#[derive(Clone, Copy)]
+pub struct Color(pub u8);
+
+impl Color {
+    const NAMES: [&'static str; 16] = [
+        "black",
+        "red",
+        "green",
+        "yellow",
+        "blue",
+        "magenta",
+        "cyan",
+        "white",
+        "light-black",
+        "light-red",
+        "light-green",
+        "light-yellow",
+        "light-blue",
+        "light-magenta",
+        "light-cyan",
+        "light-white",
+    ];
+
+    pub fn ansi(&self) -> AnsiValue {
+        return AnsiValue(self.0);
+    }
+}
+
+struct ColorVisitor;
+
+impl<'de> Visitor<'de> for ColorVisitor {
+    type Value = Color;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "a color name like \"red\" or \"light-blue\", or a number from 0 to 255"
+        )
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Color, E> {
+        match Color::NAMES.iter().position(|n| *n == v) {
+            Some(i) => return Ok(Color(i as u8)),
+            None => {
+                return Err(E::custom(format!(
+                    "unknown color \"{}\", expected one of {} or a number from 0 to 255",
+                    v,
+                    Color::NAMES.join(", ")
+                )))
+            }
+        }
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64) -> Result<Color, E> {
+        match u8::try_from(v) {
+            Ok(n) => return Ok(Color(n)),
+            Err(_) => {
+                return Err(E::custom(format!(
+                    "color number {} is out of range, expected 0 to 255",
+                    v
+                )))
+            }
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Color {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Color, D::Error> {
+        return deserializer.deserialize_any(ColorVisitor);
+    }
+}
+
+// 盤面の色。設定ファイルの[theme]で書いたものだけを置き換える
+#[derive(Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Theme {
+    pub opened_bg: Color,
+    pub unopened_fg: Color,
+    pub unopened_bg: Color,
+    pub flag_fg: Color,
+    pub question_fg: Color,
+    pub mine_fg: Color,
+    pub exploded_bg: Color,
+    pub hint_bg: Color,
+    // 1から8の数字の色
+    pub numbers: [Color; 8],
+}
+
+impl Default for Theme {
+    fn default() -> Theme {
+        return Theme {
+            opened_bg: Color(0),
+            unopened_fg: Color(7),
+            unopened_bg: Color(8),
+            flag_fg: Color(1),
+            question_fg: Color(11),
+            mine_fg: Color(7),
+            exploded_bg: Color(1),
+            hint_bg: Color(14),
+            numbers: [
+                Color(4),
+                Color(2),
+                Color(1),
+                Color(6),
+                Color(5),
+                Color(12),
+                Color(10),
+                Color(9),
+            ],
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn theme(text: &str) -> Result<Theme, String> {
+        return toml::from_str(text).map_err(|e| e.to_string());
+    }
+
+    #[test]
+    fn colors_are_names_or_numbers() {
+        let theme = theme("flag_fg = \"light-blue\"\nhint_bg = 200\n").unwrap();
+        assert_eq!(theme.flag_fg.0, 12);
+        assert_eq!(theme.hint_bg.0, 200);
+        // 書いていない色は既定値のまま
+        assert_eq!(theme.mine_fg.0, 7);
+    }
+
+    #[test]
+    fn unknown_colors_are_rejected() {
+        let msg = theme("flag_fg = \"pink\"\n").err().unwrap();
+        assert!(msg.contains("unknown color \"pink\""), "{}", msg);
+        let msg = theme("flag_fg = 256\n").err().unwrap();
+        assert!(msg.contains("color number 256 is out of range"), "{}", msg);
+        assert!(theme("flag_fg = -1\n").is_err());
+        assert!(theme("numbers = [\"red\", \"grey\"]\n").is_err());
+        assert!(theme("flag = \"red\"\n").is_err());
+    }
+}
@@ -0,0 +1,101 @@
+use std::time::{Duration, Instant};
+
+// 一時停止できる経過時間の計測
+pub struct Timer {
+    base: Duration,
+    started: Option<Instant>,
+}
+
+impl Timer {
+    pub fn construct(elapsed: Duration) -> Timer {
+        return Timer {
+            base: elapsed,
+            started: None,
+        };
+    }
+
+    pub fn start(&mut self) {
+        self._start_at(Instant::now());
+    }
+
+    pub fn stop(&mut self) {
+        self._stop_at(Instant::now());
+    }
+
+    pub fn reset(&mut self) {
+        self.base = Duration::ZERO;
+        self.started = None;
+    }
+
+    pub fn is_running(&self) -> bool {
+        return self.started.is_some();
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        return self._elapsed_at(Instant::now());
+    }
+
+    // 今の時刻を外から渡す版。テストでは好きな時刻を渡せる
+    fn _start_at(&mut self, now: Instant) {
+        if self.started.is_none() {
+            self.started = Some(now);
+        }
+    }
+
+    fn _stop_at(&mut self, now: Instant) {
+        if let Some(started) = self.started.take() {
+            self.base += now.saturating_duration_since(started);
+        }
+    }
+
+    fn _elapsed_at(&self, now: Instant) -> Duration {
+        match self.started {
+            Some(started) => self.base + now.saturating_duration_since(started),
+            None => self.base,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(n: u64) -> Duration {
+        return Duration::from_millis(n);
+    }
+
+    #[test]
+    fn pause_and_resume_add_up_the_running_time() {
+        let t0 = Instant::now();
+        let mut timer = Timer::construct(Duration::ZERO);
+        assert_eq!(timer._elapsed_at(t0 + ms(500)), Duration::ZERO);
+
+        timer._start_at(t0);
+        // 動いている間にもう一度startしても最初の時刻から数える
+        timer._start_at(t0 + ms(300));
+        assert!(timer.is_running());
+        assert_eq!(timer._elapsed_at(t0 + ms(1000)), ms(1000));
+
+        timer._stop_at(t0 + ms(1500));
+        assert!(!timer.is_running());
+        assert_eq!(timer._elapsed_at(t0 + ms(9000)), ms(1500));
+        timer._stop_at(t0 + ms(9000));
+        assert_eq!(timer._elapsed_at(t0 + ms(9000)), ms(1500));
+
+        timer._start_at(t0 + ms(10000));
+        assert_eq!(timer._elapsed_at(t0 + ms(10250)), ms(1750));
+
+        timer.reset();
+        assert!(!timer.is_running());
+        assert_eq!(timer._elapsed_at(t0 + ms(20000)), Duration::ZERO);
+    }
+
+    #[test]
+    fn resumed_game_continues_from_the_saved_time() {
+        let t0 = Instant::now();
+        let mut timer = Timer::construct(ms(42000));
+        assert_eq!(timer._elapsed_at(t0), ms(42000));
+        timer._start_at(t0);
+        assert_eq!(timer._elapsed_at(t0 + ms(3000)), ms(45000));
+    }
+}